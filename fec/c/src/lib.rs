@@ -2,9 +2,25 @@ extern crate fec;
 extern crate libc;
 
 use fec::convolutional::{Decoder, Encoder};
+use fec::reed_solomon;
+use fec::FecError;
 use libc::{size_t, ssize_t};
+use std::ptr;
 use std::slice;
 
+fn error_code(err: FecError) -> ssize_t {
+    match err {
+        FecError::InvalidLength => -1,
+        FecError::BufferTooSmall => -2,
+        FecError::InvalidRate => -3,
+        FecError::InvalidOrder => -4,
+        FecError::InvalidPolynomial => -5,
+        FecError::InvalidInterleaver => -6,
+        FecError::Uncorrectable => -7,
+        FecError::ChecksumMismatch => -8,
+    }
+}
+
 #[repr(C)]
 pub struct Convolutional {
     encoder: Encoder,
@@ -21,11 +37,12 @@ pub extern "C" fn correct_convolutional_create(
     unsafe {
         polys = slice::from_raw_parts(c_polys, rate);
     }
-    let conv = Box::new(Convolutional {
-        encoder: Encoder::new(rate as u32, order as u32, polys),
-        decoder: Decoder::new(rate as u32, order as u32, polys),
-    });
-    Box::into_raw(conv)
+    let encoder = Encoder::new(rate as u32, order as u32, polys);
+    let decoder = Decoder::new(rate as u32, order as u32, polys);
+    match (encoder, decoder) {
+        (Ok(encoder), Ok(decoder)) => Box::into_raw(Box::new(Convolutional { encoder, decoder })),
+        _ => ptr::null_mut(),
+    }
 }
 
 #[no_mangle]
@@ -60,10 +77,10 @@ pub extern "C" fn correct_convolutional_encode(
     unsafe {
         conv = &mut *conv_ptr;
         msg = slice::from_raw_parts(msg_ptr, msg_len);
-        let encode_len = conv.encoder.encode_len(msg_len) / 8 + 1;
+        let encode_len = conv.encoder.encode_len(msg_len).div_ceil(8);
         encoded = slice::from_raw_parts_mut(encoded_ptr, encode_len);
     }
-    conv.encoder.encode(msg, encoded)
+    conv.encoder.encode(msg, encoded).unwrap_or(0)
 }
 
 #[no_mangle]
@@ -78,19 +95,133 @@ pub extern "C" fn correct_convolutional_decode(
     let msg: &mut [u8];
     unsafe {
         conv = &mut *conv_ptr;
-        let encoded_len = num_encoded_bits / 8 + 1;
-        encoded = slice::from_raw_parts(encoded_ptr, encoded_len);
-        msg = slice::from_raw_parts_mut(msg_ptr, encoded_len);
+        encoded = slice::from_raw_parts(encoded_ptr, num_encoded_bits.div_ceil(8));
+        msg = slice::from_raw_parts_mut(msg_ptr, conv.decoder.decode_len(num_encoded_bits));
+    }
+    match conv.decoder.decode(encoded, num_encoded_bits, msg) {
+        Ok(len) => len as ssize_t,
+        Err(err) => error_code(err),
     }
-    conv.decoder.decode(encoded, num_encoded_bits, msg)
 }
 
 #[no_mangle]
 pub extern "C" fn correct_convolutional_decode_soft(
     conv_ptr: *mut Convolutional,
-    soft: *const u8,
+    soft_ptr: *const u8,
     num_encoded_bits: size_t,
-    msg: *mut u8,
+    msg_ptr: *mut u8,
+) -> ssize_t {
+    let conv: &mut Convolutional;
+    let soft: &[u8];
+    let msg: &mut [u8];
+    unsafe {
+        conv = &mut *conv_ptr;
+        soft = slice::from_raw_parts(soft_ptr, num_encoded_bits);
+        msg = slice::from_raw_parts_mut(msg_ptr, conv.decoder.decode_len(num_encoded_bits));
+    }
+    match conv.decoder.decode_soft(soft, num_encoded_bits, msg) {
+        Ok(len) => len as ssize_t,
+        Err(err) => error_code(err),
+    }
+}
+
+#[repr(C)]
+pub struct ReedSolomon {
+    encoder: reed_solomon::Encoder,
+    decoder: reed_solomon::Decoder,
+}
+
+#[no_mangle]
+pub extern "C" fn correct_reed_solomon_create(
+    primitive_polynomial: u16,
+    first_consecutive_root: u8,
+    generator_root_gap: u8,
+    num_roots: size_t,
+) -> *mut ReedSolomon {
+    let fcr = first_consecutive_root as usize;
+    let gap = generator_root_gap as usize;
+    let encoder = reed_solomon::Encoder::new(primitive_polynomial, fcr, gap, num_roots);
+    let decoder = reed_solomon::Decoder::new(primitive_polynomial, fcr, gap, num_roots);
+    match (encoder, decoder) {
+        (Ok(encoder), Ok(decoder)) => Box::into_raw(Box::new(ReedSolomon { encoder, decoder })),
+        _ => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn correct_reed_solomon_destroy(rs_ptr: *mut ReedSolomon) {
+    unsafe {
+        drop(Box::from_raw(rs_ptr));
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn correct_reed_solomon_encode(
+    rs_ptr: *mut ReedSolomon,
+    msg_ptr: *const u8,
+    msg_len: size_t,
+    encoded_ptr: *mut u8,
+) -> ssize_t {
+    let rs: &mut ReedSolomon;
+    let msg: &[u8];
+    let encoded: &mut [u8];
+    unsafe {
+        rs = &mut *rs_ptr;
+        msg = slice::from_raw_parts(msg_ptr, msg_len);
+        encoded = slice::from_raw_parts_mut(encoded_ptr, rs.encoder.encode_len(msg_len));
+    }
+    match rs.encoder.encode(msg, encoded) {
+        Ok(len) => len as ssize_t,
+        Err(err) => error_code(err),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn correct_reed_solomon_decode(
+    rs_ptr: *mut ReedSolomon,
+    encoded_ptr: *const u8,
+    encoded_len: size_t,
+    msg_ptr: *mut u8,
+) -> ssize_t {
+    correct_reed_solomon_decode_with_erasures(
+        rs_ptr,
+        encoded_ptr,
+        encoded_len,
+        ptr::null(),
+        0,
+        msg_ptr,
+    )
+}
+
+#[no_mangle]
+pub extern "C" fn correct_reed_solomon_decode_with_erasures(
+    rs_ptr: *mut ReedSolomon,
+    encoded_ptr: *const u8,
+    encoded_len: size_t,
+    erasure_ptr: *const u8,
+    erasure_len: size_t,
+    msg_ptr: *mut u8,
 ) -> ssize_t {
-    0
+    let rs: &mut ReedSolomon;
+    let encoded: &[u8];
+    let erasures: Vec<usize>;
+    let msg: &mut [u8];
+    unsafe {
+        rs = &mut *rs_ptr;
+        encoded = slice::from_raw_parts(encoded_ptr, encoded_len);
+        erasures = if erasure_len == 0 {
+            Vec::new()
+        } else {
+            slice::from_raw_parts(erasure_ptr, erasure_len)
+                .iter()
+                .map(|index| *index as usize)
+                .collect()
+        };
+        msg = slice::from_raw_parts_mut(msg_ptr, rs.decoder.decode_len(encoded_len));
+    }
+    // like libcorrect, report the message length rather than the number of corrections
+    match rs.decoder.decode_with_erasures(encoded, &erasures, msg) {
+        Ok(_) => rs.decoder.decode_len(encoded_len) as ssize_t,
+        Err(err) => error_code(err),
+    }
 }
@@ -1,10 +1,15 @@
 extern crate fec;
+#[macro_use]
+extern crate lazy_static;
 extern crate libc;
 
-use fec::convolutional::Decoder;
+use fec::convolutional::{Code, Decoder};
+use fec::reed_solomon;
+use fec::FecError;
 use libc::{c_int, c_uint};
-use std::iter;
+use std::ptr;
 use std::slice;
+use std::sync::Mutex;
 
 #[repr(C)]
 pub struct Shim {
@@ -17,7 +22,7 @@ pub struct Shim {
 }
 
 impl Shim {
-    fn new(num_decoded_bits: usize, rate: u32, order: u32, polys: &[u16]) -> Shim {
+    fn new(num_decoded_bits: usize, code: Code) -> Result<Shim, FecError> {
         let num_decoded: usize;
         if num_decoded_bits % 8 == 0 {
             num_decoded = num_decoded_bits / 8;
@@ -27,14 +32,14 @@ impl Shim {
 
         let decode_buffer = vec![0; num_decoded + 1];
 
-        Shim {
-            decoder: Decoder::new(rate, order, polys),
-            rate: rate,
-            order: order,
+        Ok(Shim {
+            decoder: Decoder::from_code(code)?,
+            rate: code.rate,
+            order: code.order,
             decode_buffer: decode_buffer,
             read_index: 0,
             write_index: 0,
-        }
+        })
     }
 
     fn init(&mut self) {
@@ -42,44 +47,24 @@ impl Shim {
         self.write_index = 0;
     }
 
-    fn decode(&mut self, encoded: &[u8]) {
+    fn decode(&mut self, encoded: &[u8]) -> Result<(), FecError> {
         let remaining_buffer = self.decode_buffer.len() - self.write_index;
         let remaining_bits = 8 * remaining_buffer;
         let mut encoded_bits = encoded.len();
 
-        let mut decoded_len = (encoded.len() / self.rate as usize) - (self.order as usize - 1);
+        let decoded_len = (encoded.len() / self.rate as usize) - (self.order as usize - 1);
         if decoded_len > remaining_bits {
             let over = decoded_len - remaining_bits;
-            decoded_len -= over;
             encoded_bits -= over * self.rate as usize;
         }
 
-        // XXX we need soft!
-        let mut hard = vec![0; encoded_bits / 8 + 1];
-        let extra_bits: usize;
-        if encoded_bits % 8 == 0 {
-            extra_bits = 0;
-        } else {
-            extra_bits = 8 - (encoded_bits % 8);
-        }
-        let mut soft_iter = encoded.iter().chain(iter::repeat(&0).take(extra_bits));
-        for byte in hard.iter_mut().take(encoded_bits / 8) {
-            *byte = (soft_iter.next().unwrap() & 1) << 7;
-            *byte |= (soft_iter.next().unwrap() & 1) << 6;
-            *byte |= (soft_iter.next().unwrap() & 1) << 5;
-            *byte |= (soft_iter.next().unwrap() & 1) << 4;
-            *byte |= (soft_iter.next().unwrap() & 1) << 3;
-            *byte |= (soft_iter.next().unwrap() & 1) << 2;
-            *byte |= (soft_iter.next().unwrap() & 1) << 1;
-            *byte |= (soft_iter.next().unwrap() & 1);
-        }
-
-        self.decoder.decode(
-            &hard,
+        let len = self.decoder.decode_soft(
+            &encoded[..encoded_bits],
             encoded_bits,
             &mut self.decode_buffer[self.write_index..],
-        );
-        self.write_index += decoded_len / 8;
+        )?;
+        self.write_index += len;
+        Ok(())
     }
 
     fn receive(&mut self, decoded: &mut [u8]) {
@@ -107,8 +92,10 @@ impl Shim {
 
 #[no_mangle]
 pub extern "C" fn create_viterbi27(num_decoded_bits: c_int) -> *mut Shim {
-    let shim = Box::new(Shim::new(num_decoded_bits as usize, 2, 7, &[0o155, 0o117]));
-    Box::into_raw(shim)
+    match Shim::new(num_decoded_bits as usize, Code::CCSDS_K7_R12) {
+        Ok(shim) => Box::into_raw(Box::new(shim)),
+        Err(_) => ptr::null_mut(),
+    }
 }
 
 #[no_mangle]
@@ -140,8 +127,10 @@ pub extern "C" fn update_viterbi27_blk(
         shim = &mut *shim_ptr;
         encoded = slice::from_raw_parts(encoded_ptr, num_groups as usize * shim.rate as usize);
     }
-    shim.decode(encoded);
-    0
+    match shim.decode(encoded) {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
 }
 
 #[no_mangle]
@@ -163,8 +152,10 @@ pub extern "C" fn chainback_viterbi27(
 
 #[no_mangle]
 pub extern "C" fn create_viterbi29(num_decoded_bits: c_int) -> *mut Shim {
-    let shim = Box::new(Shim::new(num_decoded_bits as usize, 2, 9, &[0o657, 0o435]));
-    Box::into_raw(shim)
+    match Shim::new(num_decoded_bits as usize, Code::NASA_K9_R12) {
+        Ok(shim) => Box::into_raw(Box::new(shim)),
+        Err(_) => ptr::null_mut(),
+    }
 }
 
 #[no_mangle]
@@ -196,8 +187,10 @@ pub extern "C" fn update_viterbi29_blk(
         shim = &mut *shim_ptr;
         encoded = slice::from_raw_parts(encoded_ptr, num_groups as usize * shim.rate as usize);
     }
-    shim.decode(encoded);
-    0
+    match shim.decode(encoded) {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
 }
 
 #[no_mangle]
@@ -219,13 +212,10 @@ pub extern "C" fn chainback_viterbi29(
 
 #[no_mangle]
 pub extern "C" fn create_viterbi39(num_decoded_bits: c_int) -> *mut Shim {
-    let shim = Box::new(Shim::new(
-        num_decoded_bits as usize,
-        3,
-        9,
-        &[0o755, 0o633, 0o447],
-    ));
-    Box::into_raw(shim)
+    match Shim::new(num_decoded_bits as usize, Code::NASA_K9_R13) {
+        Ok(shim) => Box::into_raw(Box::new(shim)),
+        Err(_) => ptr::null_mut(),
+    }
 }
 
 #[no_mangle]
@@ -257,8 +247,10 @@ pub extern "C" fn update_viterbi39_blk(
         shim = &mut *shim_ptr;
         encoded = slice::from_raw_parts(encoded_ptr, num_groups as usize * shim.rate as usize);
     }
-    shim.decode(encoded);
-    0
+    match shim.decode(encoded) {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
 }
 
 #[no_mangle]
@@ -280,13 +272,10 @@ pub extern "C" fn chainback_viterbi39(
 
 #[no_mangle]
 pub extern "C" fn create_viterbi615(num_decoded_bits: c_int) -> *mut Shim {
-    let shim = Box::new(Shim::new(
-        num_decoded_bits as usize,
-        6,
-        15,
-        &[0o42631, 0o47245, 0o56507, 0o73363, 0o77267, 0o64537],
-    ));
-    Box::into_raw(shim)
+    match Shim::new(num_decoded_bits as usize, Code::CASSINI_K15_R16) {
+        Ok(shim) => Box::into_raw(Box::new(shim)),
+        Err(_) => ptr::null_mut(),
+    }
 }
 
 #[no_mangle]
@@ -318,8 +307,10 @@ pub extern "C" fn update_viterbi615_blk(
         shim = &mut *shim_ptr;
         encoded = slice::from_raw_parts(encoded_ptr, num_groups as usize * shim.rate as usize);
     }
-    shim.decode(encoded);
-    0
+    match shim.decode(encoded) {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
 }
 
 #[no_mangle]
@@ -338,3 +329,321 @@ pub extern "C" fn chainback_viterbi615(
     shim.receive(decoded);
     0
 }
+
+/// Rows of the CCSDS dual-basis conversion matrix
+const CCSDS_TAL: [u8; 8] = [0x8d, 0xef, 0xec, 0x86, 0xfa, 0x99, 0xaf, 0x7b];
+
+#[repr(C)]
+pub struct ReedSolomon {
+    encoder: reed_solomon::Encoder,
+    decoder: reed_solomon::Decoder,
+    num_roots: usize,
+    pad: usize,
+    erasures: Vec<usize>,
+    positions: Vec<c_int>,
+    msg: Vec<u8>,
+    block: Vec<u8>,
+}
+
+impl ReedSolomon {
+    fn new(code: reed_solomon::Code, pad: usize) -> Result<ReedSolomon, FecError> {
+        let encoder = reed_solomon::Encoder::from_code(code)?;
+        let decoder = reed_solomon::Decoder::from_code(code)?;
+        let num_roots = code.num_roots;
+        if num_roots + pad >= reed_solomon::MAX_BLOCK_LEN {
+            return Err(FecError::InvalidLength);
+        }
+
+        Ok(ReedSolomon {
+            encoder,
+            decoder,
+            num_roots,
+            pad,
+            erasures: Vec::with_capacity(num_roots),
+            positions: Vec::with_capacity(num_roots),
+            msg: vec![0; reed_solomon::MAX_BLOCK_LEN],
+            block: vec![0; reed_solomon::MAX_BLOCK_LEN],
+        })
+    }
+
+    fn data_len(&self, pad: usize) -> usize {
+        reed_solomon::MAX_BLOCK_LEN - self.num_roots - pad
+    }
+
+    fn encode(&mut self, data: &[u8], parity: &mut [u8]) -> Result<(), FecError> {
+        let block_len = self.encoder.encode(data, &mut self.block)?;
+        parity.clone_from_slice(&self.block[data.len()..block_len]);
+        Ok(())
+    }
+
+    // libfec counts erasure positions in the full 255 byte block, padding
+    // included, both those it is given and those it reports, and corrects the
+    // block in place. Like the count, the reported positions cover every root
+    // of the errata locator, even erasures that turned out to be right.
+    fn decode(
+        &mut self,
+        block: &mut [u8],
+        pad: usize,
+        erasures: &[c_int],
+    ) -> Result<usize, FecError> {
+        self.erasures.clear();
+        for position in erasures {
+            if *position < pad as c_int {
+                return Err(FecError::InvalidLength);
+            }
+            self.erasures.push(*position as usize - pad);
+        }
+
+        let msg_len = self.decoder.decode_len(block.len());
+        self.decoder
+            .decode_with_erasures(block, &self.erasures, &mut self.msg)?;
+        // the decoder only returns the message, so encode it again for the parity
+        let block_len = self.encoder.encode(&self.msg[..msg_len], &mut self.block)?;
+        block.copy_from_slice(&self.block[..block_len]);
+
+        self.positions.clear();
+        self.positions.extend(
+            self.decoder
+                .errata_positions()
+                .map(|index| (index + pad) as c_int),
+        );
+        Ok(self.positions.len())
+    }
+}
+
+struct Ccsds {
+    rs: ReedSolomon,
+    // conventional to dual basis, and back
+    taltab: [u8; 256],
+    tal1tab: [u8; 256],
+}
+
+impl Ccsds {
+    fn new() -> Ccsds {
+        let mut taltab = [0; 256];
+        let mut tal1tab = [0; 256];
+        for (i, dual) in taltab.iter_mut().enumerate() {
+            for (k, row) in CCSDS_TAL.iter().rev().enumerate() {
+                if i & (1 << k) != 0 {
+                    *dual ^= row;
+                }
+            }
+            tal1tab[*dual as usize] = i as u8;
+        }
+
+        Ccsds {
+            rs: ReedSolomon::new(reed_solomon::Code::CCSDS, 0).expect("CCSDS parameters are valid"),
+            taltab,
+            tal1tab,
+        }
+    }
+}
+
+lazy_static! {
+    static ref CCSDS: Mutex<Ccsds> = Mutex::new(Ccsds::new());
+}
+
+fn check_ccsds_pad(pad: c_int) -> Option<usize> {
+    if pad < 0 || pad as usize >= reed_solomon::MAX_BLOCK_LEN - reed_solomon::Code::CCSDS.num_roots
+    {
+        None
+    } else {
+        Some(pad as usize)
+    }
+}
+
+fn write_positions(rs: &ReedSolomon, eras_pos: *mut c_int) {
+    if eras_pos.is_null() {
+        return;
+    }
+    let positions: &mut [c_int];
+    unsafe {
+        positions = slice::from_raw_parts_mut(eras_pos, rs.positions.len());
+    }
+    positions.clone_from_slice(&rs.positions);
+}
+
+fn read_erasures(eras_pos: *const c_int, no_eras: c_int) -> Vec<c_int> {
+    if eras_pos.is_null() || no_eras <= 0 {
+        return Vec::new();
+    }
+    unsafe { slice::from_raw_parts(eras_pos, no_eras as usize).to_vec() }
+}
+
+#[no_mangle]
+pub extern "C" fn init_rs_char(
+    symsize: c_int,
+    gfpoly: c_int,
+    fcr: c_int,
+    prim: c_int,
+    nroots: c_int,
+    pad: c_int,
+) -> *mut ReedSolomon {
+    // only byte symbols are supported
+    if symsize != 8 || gfpoly < 0 || fcr < 0 || prim < 0 || nroots < 0 || pad < 0 {
+        return ptr::null_mut();
+    }
+    let code = reed_solomon::Code {
+        primitive_polynomial: gfpoly as u16,
+        first_consecutive_root: fcr as usize,
+        root_gap: prim as usize,
+        num_roots: nroots as usize,
+    };
+    match ReedSolomon::new(code, pad as usize) {
+        Ok(rs) => Box::into_raw(Box::new(rs)),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn free_rs_char(rs_ptr: *mut ReedSolomon) {
+    unsafe {
+        drop(Box::from_raw(rs_ptr));
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn encode_rs_char(
+    rs_ptr: *mut ReedSolomon,
+    data_ptr: *const u8,
+    parity_ptr: *mut u8,
+) {
+    let rs: &mut ReedSolomon;
+    let data: &[u8];
+    let parity: &mut [u8];
+    unsafe {
+        rs = &mut *rs_ptr;
+        data = slice::from_raw_parts(data_ptr, rs.data_len(rs.pad));
+        parity = slice::from_raw_parts_mut(parity_ptr, rs.num_roots);
+    }
+    rs.encode(data, parity)
+        .expect("block length is checked by init_rs_char");
+}
+
+#[no_mangle]
+pub extern "C" fn decode_rs_char(
+    rs_ptr: *mut ReedSolomon,
+    data_ptr: *mut u8,
+    eras_pos: *mut c_int,
+    no_eras: c_int,
+) -> c_int {
+    let rs: &mut ReedSolomon;
+    let block: &mut [u8];
+    let erasures = read_erasures(eras_pos, no_eras);
+    unsafe {
+        rs = &mut *rs_ptr;
+        block = slice::from_raw_parts_mut(data_ptr, reed_solomon::MAX_BLOCK_LEN - rs.pad);
+    }
+    let pad = rs.pad;
+    match rs.decode(block, pad, &erasures) {
+        Ok(count) => {
+            write_positions(rs, eras_pos);
+            count as c_int
+        }
+        Err(_) => -1,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn encode_rs_8(data_ptr: *const u8, parity_ptr: *mut u8, pad: c_int) {
+    let pad = match check_ccsds_pad(pad) {
+        Some(pad) => pad,
+        None => return,
+    };
+    let mut ccsds = CCSDS.lock().unwrap();
+    let data: &[u8];
+    let parity: &mut [u8];
+    unsafe {
+        data = slice::from_raw_parts(data_ptr, ccsds.rs.data_len(pad));
+        parity = slice::from_raw_parts_mut(parity_ptr, ccsds.rs.num_roots);
+    }
+    ccsds.rs.encode(data, parity).expect("pad is checked");
+}
+
+#[no_mangle]
+pub extern "C" fn decode_rs_8(
+    data_ptr: *mut u8,
+    eras_pos: *mut c_int,
+    no_eras: c_int,
+    pad: c_int,
+) -> c_int {
+    let pad = match check_ccsds_pad(pad) {
+        Some(pad) => pad,
+        None => return -1,
+    };
+    let mut ccsds = CCSDS.lock().unwrap();
+    let block: &mut [u8];
+    let erasures = read_erasures(eras_pos, no_eras);
+    unsafe {
+        block = slice::from_raw_parts_mut(data_ptr, reed_solomon::MAX_BLOCK_LEN - pad);
+    }
+    match ccsds.rs.decode(block, pad, &erasures) {
+        Ok(count) => {
+            write_positions(&ccsds.rs, eras_pos);
+            count as c_int
+        }
+        Err(_) => -1,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn encode_rs_ccsds(data_ptr: *const u8, parity_ptr: *mut u8, pad: c_int) {
+    let pad = match check_ccsds_pad(pad) {
+        Some(pad) => pad,
+        None => return,
+    };
+    let mut ccsds = CCSDS.lock().unwrap();
+    let data: &[u8];
+    let parity: &mut [u8];
+    unsafe {
+        data = slice::from_raw_parts(data_ptr, ccsds.rs.data_len(pad));
+        parity = slice::from_raw_parts_mut(parity_ptr, ccsds.rs.num_roots);
+    }
+
+    let conventional: Vec<u8> = data
+        .iter()
+        .map(|byte| ccsds.tal1tab[*byte as usize])
+        .collect();
+    ccsds
+        .rs
+        .encode(&conventional, parity)
+        .expect("pad is checked");
+    for byte in parity.iter_mut() {
+        *byte = ccsds.taltab[*byte as usize];
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn decode_rs_ccsds(
+    data_ptr: *mut u8,
+    eras_pos: *mut c_int,
+    no_eras: c_int,
+    pad: c_int,
+) -> c_int {
+    let pad = match check_ccsds_pad(pad) {
+        Some(pad) => pad,
+        None => return -1,
+    };
+    let mut ccsds = CCSDS.lock().unwrap();
+    let block: &mut [u8];
+    let erasures = read_erasures(eras_pos, no_eras);
+    unsafe {
+        block = slice::from_raw_parts_mut(data_ptr, reed_solomon::MAX_BLOCK_LEN - pad);
+    }
+
+    let mut conventional: Vec<u8> = block
+        .iter()
+        .map(|byte| ccsds.tal1tab[*byte as usize])
+        .collect();
+    let result = ccsds.rs.decode(&mut conventional, pad, &erasures);
+    for (byte, corrected) in block.iter_mut().zip(conventional.iter()) {
+        *byte = ccsds.taltab[*corrected as usize];
+    }
+    match result {
+        Ok(count) => {
+            write_positions(&ccsds.rs, eras_pos);
+            count as c_int
+        }
+        Err(_) => -1,
+    }
+}
@@ -0,0 +1,340 @@
+//! Concatenated Reed-Solomon and convolutional coding
+//!
+//! The outer Reed-Solomon code splits the message into `depth` codewords,
+//! whose bytes are interleaved one at a time before the inner convolutional
+//! code encodes the whole frame, as in the CCSDS telemetry standard. Viterbi
+//! decoding errors come in bursts, and the interleaver spreads each burst over
+//! every codeword so that none of them gets more than it can correct.
+
+use convolutional::{self, Code};
+use interleave::Interleaver;
+use reed_solomon;
+use FecError;
+
+use std::error::Error;
+use std::fmt;
+
+/// Layer of a `Concatenated` codec that an error came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layer {
+    /// The length of the encoded frame or of the output buffer
+    Frame,
+    /// The inner convolutional code
+    Inner,
+    /// An outer Reed-Solomon codeword, by its index in the frame
+    Outer(usize),
+}
+
+/// Error returned by `Concatenated`, along with the layer that failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayerError {
+    pub layer: Layer,
+    pub error: FecError,
+}
+
+impl fmt::Display for LayerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.layer {
+            Layer::Frame => write!(f, "frame: {}", self.error),
+            Layer::Inner => write!(f, "inner code: {}", self.error),
+            Layer::Outer(codeword) => write!(f, "outer codeword {}: {}", codeword, self.error),
+        }
+    }
+}
+
+impl Error for LayerError {}
+
+fn frame_error(error: FecError) -> LayerError {
+    LayerError {
+        layer: Layer::Frame,
+        error,
+    }
+}
+
+fn inner_error(error: FecError) -> LayerError {
+    LayerError {
+        layer: Layer::Inner,
+        error,
+    }
+}
+
+/// Outer Reed-Solomon code, block interleaver and inner convolutional code
+///
+/// Every frame holds `msg_len` message bytes. These are split into `depth`
+/// consecutive chunks, each encoded into a Reed-Solomon codeword of
+/// `block_len` bytes; byte `j` of codeword `i` is then sent at position
+/// `j * depth + i` of the inner frame.
+#[derive(Debug)]
+pub struct Concatenated {
+    outer_encoder: reed_solomon::Encoder,
+    outer_decoder: reed_solomon::Decoder,
+    inner_encoder: convolutional::Encoder,
+    inner_decoder: convolutional::Decoder,
+    interleaver: Interleaver,
+    block_len: usize,
+    num_roots: usize,
+    depth: usize,
+    codewords: Vec<u8>,
+    frame: Vec<u8>,
+}
+
+impl Concatenated {
+    /// Create a codec with the inner convolutional `code`, and the outer
+    /// Reed-Solomon code `outer_code` shortened to `block_len` bytes and
+    /// interleaved to `depth` codewords
+    pub fn new(
+        code: Code,
+        outer_code: reed_solomon::Code,
+        block_len: usize,
+        depth: usize,
+    ) -> Result<Concatenated, FecError> {
+        let outer_encoder = reed_solomon::Encoder::from_code(outer_code)?;
+        let outer_decoder = reed_solomon::Decoder::from_code(outer_code)?;
+        let num_roots = outer_code.num_roots;
+        if block_len <= num_roots || block_len > reed_solomon::MAX_BLOCK_LEN || depth == 0 {
+            return Err(FecError::InvalidLength);
+        }
+
+        Ok(Concatenated {
+            outer_encoder,
+            outer_decoder,
+            inner_encoder: convolutional::Encoder::from_code(code)?,
+            inner_decoder: convolutional::Decoder::from_code(code)?,
+            interleaver: Interleaver::block(depth, block_len)?,
+            block_len,
+            num_roots,
+            depth,
+            codewords: vec![0; depth * block_len],
+            frame: vec![0; depth * block_len],
+        })
+    }
+
+    /// Create a codec with the CCSDS (255,223) Reed-Solomon code, interleaved
+    /// to `depth` codewords, and the CCSDS K=7 rate 1/2 convolutional code.
+    /// Unlike CCSDS, the Reed-Solomon symbols are in the conventional basis.
+    pub fn ccsds(depth: usize) -> Result<Concatenated, FecError> {
+        Concatenated::new(
+            Code::CCSDS_K7_R12,
+            reed_solomon::Code::CCSDS,
+            reed_solomon::MAX_BLOCK_LEN,
+            depth,
+        )
+    }
+
+    /// Length in bytes of the messages this codec takes
+    pub fn msg_len(&self) -> usize {
+        self.depth * (self.block_len - self.num_roots)
+    }
+
+    /// Number of encoded bits in a frame
+    pub fn encode_len(&self) -> usize {
+        self.inner_encoder.encode_len(self.frame.len())
+    }
+
+    /// Encode `msg`, which must be `msg_len` bytes long, and return the
+    /// number of encoded bits
+    pub fn encode(&mut self, msg: &[u8], dst: &mut [u8]) -> Result<usize, FecError> {
+        if msg.len() != self.msg_len() {
+            return Err(FecError::InvalidLength);
+        }
+        let encode_len = self.encode_len();
+        if 8 * dst.len() < encode_len {
+            return Err(FecError::BufferTooSmall);
+        }
+
+        let chunk_len = self.block_len - self.num_roots;
+        for (chunk, codeword) in msg
+            .chunks(chunk_len)
+            .zip(self.codewords.chunks_mut(self.block_len))
+        {
+            self.outer_encoder.encode(chunk, codeword)?;
+        }
+        self.interleaver
+            .interleave(&self.codewords, &mut self.frame)?;
+
+        self.inner_encoder.encode(&self.frame, dst)
+    }
+
+    /// Deinterleave the inner frame and decode every outer codeword into `msg`
+    fn decode_outer(&mut self, msg: &mut [u8]) -> Result<usize, LayerError> {
+        self.interleaver
+            .deinterleave(&self.frame, &mut self.codewords)
+            .map_err(frame_error)?;
+
+        let chunk_len = self.block_len - self.num_roots;
+        let codewords = self.codewords.chunks(self.block_len);
+        for (i, (codeword, chunk)) in codewords.zip(msg.chunks_mut(chunk_len)).enumerate() {
+            self.outer_decoder
+                .decode(codeword, chunk)
+                .map_err(|error| LayerError {
+                    layer: Layer::Outer(i),
+                    error,
+                })?;
+        }
+        Ok(self.msg_len())
+    }
+
+    fn check_msg(&self, msg: &[u8]) -> Result<(), LayerError> {
+        if msg.len() < self.msg_len() {
+            return Err(frame_error(FecError::BufferTooSmall));
+        }
+        Ok(())
+    }
+
+    /// Decode a frame of packed hard bits, `encode_len` bits long. Returns the
+    /// number of decoded bytes, or the error of the first layer that failed.
+    pub fn decode(&mut self, encoded: &[u8], msg: &mut [u8]) -> Result<usize, LayerError> {
+        let encode_len = self.encode_len();
+        if encoded.len() != encode_len.div_ceil(8) {
+            return Err(frame_error(FecError::InvalidLength));
+        }
+        self.check_msg(msg)?;
+
+        self.inner_decoder
+            .decode(encoded, encode_len, &mut self.frame)
+            .map_err(inner_error)?;
+        self.decode_outer(msg)
+    }
+
+    /// Decode soft symbols, one byte per encoded bit, where 0 is a strong zero
+    /// and 255 is a strong one. Returns the number of decoded bytes, or the
+    /// error of the first layer that failed.
+    pub fn decode_soft(&mut self, soft: &[u8], msg: &mut [u8]) -> Result<usize, LayerError> {
+        let encode_len = self.encode_len();
+        if soft.len() != encode_len {
+            return Err(frame_error(FecError::InvalidLength));
+        }
+        self.check_msg(msg)?;
+
+        self.inner_decoder
+            .decode_soft(soft, encode_len, &mut self.frame)
+            .map_err(inner_error)?;
+        self.decode_outer(msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Concatenated, Layer, LayerError};
+    use convolutional::{self, Code};
+    use FecError;
+
+    /// xorshift32, so that the noise is the same on every run
+    fn next_random(state: &mut u32) -> u32 {
+        *state ^= *state << 13;
+        *state ^= *state >> 17;
+        *state ^= *state << 5;
+        *state
+    }
+
+    fn encode(codec: &mut Concatenated, random: &mut u32) -> (Vec<u8>, Vec<u8>) {
+        let msg: Vec<u8> = (0..codec.msg_len())
+            .map(|_| next_random(random) as u8)
+            .collect();
+        let mut encoded = vec![0; codec.encode_len().div_ceil(8)];
+        assert_eq!(codec.encode(&msg, &mut encoded), Ok(codec.encode_len()));
+        (msg, encoded)
+    }
+
+    #[test]
+    fn ccsds_round_trip() {
+        let mut random = 0x6a09_e667;
+        for depth in 1..=5 {
+            let mut codec = Concatenated::ccsds(depth).unwrap();
+            assert_eq!(codec.msg_len(), depth * 223);
+            let (msg, encoded) = encode(&mut codec, &mut random);
+
+            let mut decoded = vec![0; codec.msg_len()];
+            assert_eq!(codec.decode(&encoded, &mut decoded), Ok(msg.len()));
+            assert_eq!(decoded, msg);
+
+            let soft: Vec<u8> = (0..codec.encode_len())
+                .map(|i| 255 * ((encoded[i / 8] >> (7 - i % 8)) & 1))
+                .collect();
+            let mut decoded = vec![0; codec.msg_len()];
+            assert_eq!(codec.decode_soft(&soft, &mut decoded), Ok(msg.len()));
+            assert_eq!(decoded, msg);
+        }
+    }
+
+    #[test]
+    fn corrects_bursts_longer_than_one_codeword_can() {
+        let mut random = 0xbb67_ae85;
+        let depth = 5;
+        let mut codec = Concatenated::ccsds(depth).unwrap();
+        let (msg, mut encoded) = encode(&mut codec, &mut random);
+        let frame = codec.frame.clone();
+
+        // scramble the channel bits of 60 consecutive frame bytes, which leaves
+        // 12 errors per codeword after deinterleaving, against 16 correctable
+        for byte in &mut encoded[2 * 300..2 * 360] {
+            *byte = next_random(&mut random) as u8;
+        }
+
+        let mut decoded = vec![0; codec.msg_len()];
+        assert_eq!(codec.decode(&encoded, &mut decoded), Ok(msg.len()));
+        assert_eq!(decoded, msg);
+
+        let frame_errors = frame
+            .iter()
+            .zip(&codec.frame)
+            .filter(|&(sent, received)| sent != received)
+            .count();
+        assert!(frame_errors > 16, "{} frame errors", frame_errors);
+    }
+
+    #[test]
+    fn reports_the_uncorrectable_codeword() {
+        let mut random = 0x3c6e_f372;
+        let depth = 4;
+        let mut codec = Concatenated::ccsds(depth).unwrap();
+        encode(&mut codec, &mut random);
+        let sent = codec.frame.clone();
+
+        // corrupt 20 bytes of one codeword before the inner code, so that the
+        // inner decoder passes them on intact
+        for outer in 0..depth {
+            let mut frame = sent.clone();
+            for j in 0..20 {
+                frame[10 * j * depth + outer] ^= 0x5a;
+            }
+            let mut inner = convolutional::Encoder::from_code(Code::CCSDS_K7_R12).unwrap();
+            let mut encoded = vec![0; codec.encode_len().div_ceil(8)];
+            inner.encode(&frame, &mut encoded).unwrap();
+
+            let mut decoded = vec![0; codec.msg_len()];
+            assert_eq!(
+                codec.decode(&encoded, &mut decoded),
+                Err(LayerError {
+                    layer: Layer::Outer(outer),
+                    error: FecError::Uncorrectable,
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_bad_lengths() {
+        let mut codec = Concatenated::ccsds(2).unwrap();
+        let mut encoded = vec![0; codec.encode_len().div_ceil(8)];
+        assert_eq!(
+            codec.encode(&[0; 445], &mut encoded),
+            Err(FecError::InvalidLength)
+        );
+
+        let mut decoded = vec![0; codec.msg_len()];
+        assert_eq!(
+            codec
+                .decode(&encoded[1..], &mut decoded)
+                .map_err(|e| e.layer),
+            Err(Layer::Frame)
+        );
+        assert_eq!(
+            codec.decode(&encoded, &mut decoded[1..]),
+            Err(LayerError {
+                layer: Layer::Frame,
+                error: FecError::BufferTooSmall,
+            })
+        );
+    }
+}
@@ -0,0 +1,260 @@
+//! Add-compare-select kernels for the inner loop of the Viterbi decoder
+//!
+//! Each kernel computes, for every state, the error of arriving from its low and
+//! high predecessor, keeps the smaller of the two and records which one won. All
+//! kernels produce identical results; the vector ones just handle several states
+//! at once. The scalar kernel is `Decoder::inner_step_scalar`, which also covers
+//! trellises of fewer than `BLOCK_LEN` states.
+//!
+//! Because the code is linear, the outputs of the states `base..base + BLOCK_LEN`
+//! are those of `0..BLOCK_LEN` xored with the output of `base`. The branch metrics
+//! of a block therefore only depend on the output of its first state, and are
+//! looked up as a whole from a table of `num_outputs * BLOCK_LEN` metrics built
+//! once per step by `fill_branch_table`.
+
+/// Number of consecutive states whose branch metrics are looked up together
+pub const BLOCK_LEN: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcsKernel {
+    Scalar,
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    Sse2,
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    Avx2,
+    #[cfg(target_arch = "aarch64")]
+    Neon,
+}
+
+impl AcsKernel {
+    /// Pick the widest kernel that the CPU supports for a trellis of `num_states` states
+    pub fn detect(num_states: usize) -> AcsKernel {
+        if num_states % BLOCK_LEN != 0 {
+            return AcsKernel::Scalar;
+        }
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                return AcsKernel::Avx2;
+            }
+            if is_x86_feature_detected!("sse2") {
+                return AcsKernel::Sse2;
+            }
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            if is_aarch64_feature_detected!("neon") {
+                return AcsKernel::Neon;
+            }
+        }
+        AcsKernel::Scalar
+    }
+}
+
+/// Fill `branch_table[output * BLOCK_LEN + j]` with the branch metric of state `j`
+/// in a block whose first state has the given `output`
+pub fn fill_branch_table(branch_table: &mut [u16], distances: &[u16], poly_table: &[u16]) {
+    for (output, branches) in branch_table.chunks_mut(BLOCK_LEN).enumerate() {
+        for (branch, block_output) in branches.iter_mut().zip(poly_table) {
+            *branch = distances[output ^ *block_output as usize];
+        }
+    }
+}
+
+/// Run one trellis step over all `errors.len()` states with one of the vector
+/// kernels. `poly_table` holds the output of every shift register state, and the
+/// high predecessor of a state is `num_states / 2` above its low one.
+pub fn add_compare_select(
+    kernel: AcsKernel,
+    branch_table: &[u16],
+    poly_table: &[u16],
+    previous_errors: &[u16],
+    errors: &mut [u16],
+    history: &mut [u8],
+) {
+    let num_states = errors.len();
+    assert!(num_states % BLOCK_LEN == 0 && poly_table.len() >= 2 * num_states);
+    assert!(previous_errors.len() >= num_states && history.len() >= num_states);
+
+    unsafe {
+        match kernel {
+            AcsKernel::Scalar => unreachable!("the scalar kernel has no branch table"),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            AcsKernel::Sse2 => {
+                add_compare_select_sse2(branch_table, poly_table, previous_errors, errors, history)
+            }
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            AcsKernel::Avx2 => {
+                add_compare_select_avx2(branch_table, poly_table, previous_errors, errors, history)
+            }
+            #[cfg(target_arch = "aarch64")]
+            AcsKernel::Neon => {
+                add_compare_select_neon(branch_table, poly_table, previous_errors, errors, history)
+            }
+        }
+    }
+}
+
+fn block_branches(branch_table: &[u16], block_output: usize) -> &[u16] {
+    &branch_table[block_output * BLOCK_LEN..(block_output + 1) * BLOCK_LEN]
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "sse2")]
+unsafe fn add_compare_select_sse2(
+    branch_table: &[u16],
+    poly_table: &[u16],
+    previous_errors: &[u16],
+    errors: &mut [u16],
+    history: &mut [u8],
+) {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    let num_states = errors.len();
+    let high_prev_offset = num_states / 2;
+    let high_output = poly_table[num_states] as usize;
+    let zero = _mm_setzero_si128();
+    let one = _mm_set1_epi16(1);
+
+    for block in (0..num_states).step_by(BLOCK_LEN) {
+        let block_output = poly_table[block] as usize;
+        let low_branches = block_branches(branch_table, block_output);
+        let high_branches = block_branches(branch_table, block_output ^ high_output);
+
+        for offset in (0..BLOCK_LEN).step_by(8) {
+            let state = block + offset;
+
+            // each predecessor feeds two consecutive states
+            let prev = previous_errors.as_ptr().add(state / 2);
+            let low_prev = _mm_loadl_epi64(prev as *const __m128i);
+            let low_prev = _mm_unpacklo_epi16(low_prev, low_prev);
+            let high_prev = _mm_loadl_epi64(prev.add(high_prev_offset) as *const __m128i);
+            let high_prev = _mm_unpacklo_epi16(high_prev, high_prev);
+
+            let low_error = _mm_loadu_si128(low_branches[offset..].as_ptr() as *const __m128i);
+            let low_error = _mm_add_epi16(low_error, low_prev);
+            let high_error = _mm_loadu_si128(high_branches[offset..].as_ptr() as *const __m128i);
+            let high_error = _mm_add_epi16(high_error, high_prev);
+
+            // there is no unsigned 16 bit compare, but low <= high exactly when the
+            // saturating difference is zero, and the difference also gives the minimum
+            let excess = _mm_subs_epu16(low_error, high_error);
+            let error = _mm_sub_epi16(low_error, excess);
+            let successor = _mm_andnot_si128(_mm_cmpeq_epi16(excess, zero), one);
+
+            _mm_storeu_si128(errors.as_mut_ptr().add(state) as *mut __m128i, error);
+            _mm_storel_epi64(
+                history.as_mut_ptr().add(state) as *mut __m128i,
+                _mm_packus_epi16(successor, zero),
+            );
+        }
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn add_compare_select_avx2(
+    branch_table: &[u16],
+    poly_table: &[u16],
+    previous_errors: &[u16],
+    errors: &mut [u16],
+    history: &mut [u8],
+) {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    let num_states = errors.len();
+    let high_prev_offset = num_states / 2;
+    let high_output = poly_table[num_states] as usize;
+    let zero = _mm256_setzero_si256();
+    let one = _mm256_set1_epi16(1);
+
+    for state in (0..num_states).step_by(BLOCK_LEN) {
+        let block_output = poly_table[state] as usize;
+        let low_branches = block_branches(branch_table, block_output);
+        let high_branches = block_branches(branch_table, block_output ^ high_output);
+
+        // each predecessor feeds two consecutive states
+        let prev = previous_errors.as_ptr().add(state / 2);
+        let low_prev = _mm_loadu_si128(prev as *const __m128i);
+        let low_prev = _mm256_inserti128_si256(
+            _mm256_castsi128_si256(_mm_unpacklo_epi16(low_prev, low_prev)),
+            _mm_unpackhi_epi16(low_prev, low_prev),
+            1,
+        );
+        let high_prev = _mm_loadu_si128(prev.add(high_prev_offset) as *const __m128i);
+        let high_prev = _mm256_inserti128_si256(
+            _mm256_castsi128_si256(_mm_unpacklo_epi16(high_prev, high_prev)),
+            _mm_unpackhi_epi16(high_prev, high_prev),
+            1,
+        );
+
+        let low_error = _mm256_loadu_si256(low_branches.as_ptr() as *const __m256i);
+        let low_error = _mm256_add_epi16(low_error, low_prev);
+        let high_error = _mm256_loadu_si256(high_branches.as_ptr() as *const __m256i);
+        let high_error = _mm256_add_epi16(high_error, high_prev);
+
+        let excess = _mm256_subs_epu16(low_error, high_error);
+        let error = _mm256_sub_epi16(low_error, excess);
+        let successor = _mm256_andnot_si256(_mm256_cmpeq_epi16(excess, zero), one);
+
+        _mm256_storeu_si256(errors.as_mut_ptr().add(state) as *mut __m256i, error);
+        // packing works within each 128 bit lane, so gather the low half of each lane
+        let successor = _mm256_packus_epi16(successor, zero);
+        let successor = _mm256_permute4x64_epi64(successor, 0b00_00_10_00);
+        _mm_storeu_si128(
+            history.as_mut_ptr().add(state) as *mut __m128i,
+            _mm256_castsi256_si128(successor),
+        );
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn add_compare_select_neon(
+    branch_table: &[u16],
+    poly_table: &[u16],
+    previous_errors: &[u16],
+    errors: &mut [u16],
+    history: &mut [u8],
+) {
+    use std::arch::aarch64::*;
+
+    let num_states = errors.len();
+    let high_prev_offset = num_states / 2;
+    let high_output = poly_table[num_states] as usize;
+    let one = vdupq_n_u16(1);
+
+    for block in (0..num_states).step_by(BLOCK_LEN) {
+        let block_output = poly_table[block] as usize;
+        let low_branches = block_branches(branch_table, block_output);
+        let high_branches = block_branches(branch_table, block_output ^ high_output);
+
+        for offset in (0..BLOCK_LEN).step_by(8) {
+            let state = block + offset;
+
+            // each predecessor feeds two consecutive states
+            let prev = previous_errors.as_ptr().add(state / 2);
+            let low_prev = vld1_u16(prev);
+            let low_prev = vzip_u16(low_prev, low_prev);
+            let low_prev = vcombine_u16(low_prev.0, low_prev.1);
+            let high_prev = vld1_u16(prev.add(high_prev_offset));
+            let high_prev = vzip_u16(high_prev, high_prev);
+            let high_prev = vcombine_u16(high_prev.0, high_prev.1);
+
+            let low_error = vaddq_u16(vld1q_u16(low_branches[offset..].as_ptr()), low_prev);
+            let high_error = vaddq_u16(vld1q_u16(high_branches[offset..].as_ptr()), high_prev);
+
+            let error = vminq_u16(low_error, high_error);
+            let successor = vandq_u16(vcgtq_u16(low_error, high_error), one);
+
+            vst1q_u16(errors.as_mut_ptr().add(state), error);
+            vst1_u8(history.as_mut_ptr().add(state), vmovn_u16(successor));
+        }
+    }
+}
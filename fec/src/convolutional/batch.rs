@@ -0,0 +1,45 @@
+use rayon::prelude::*;
+
+use super::Decoder;
+use FecError;
+
+/// One independent, terminated frame for `Decoder::decode_batch`
+#[derive(Debug, Clone, Copy)]
+pub enum Frame<'a> {
+    /// Packed encoded bits and the number of encoded bits in the frame
+    Hard(&'a [u8], usize),
+    /// Soft symbols, one byte per encoded bit, and the number of encoded bits
+    Soft(&'a [u8], usize),
+}
+
+impl Decoder {
+    /// Decode many independent frames across the rayon thread pool. Each worker
+    /// decodes with its own copy of this decoder's tables, and results are
+    /// returned in the same order as `frames`.
+    pub fn decode_batch(&self, frames: &[Frame]) -> Vec<Result<Vec<u8>, FecError>> {
+        frames
+            .par_iter()
+            .map_init(
+                || self.clone(),
+                |decoder, frame| {
+                    let (mut msg, decoded_len) = match *frame {
+                        Frame::Hard(encoded, num_encoded_bits) => {
+                            let mut msg = vec![0; decoder.decode_len(num_encoded_bits)];
+                            let decoded_len =
+                                decoder.decode(encoded, num_encoded_bits, &mut msg)?;
+                            (msg, decoded_len)
+                        }
+                        Frame::Soft(soft, num_encoded_bits) => {
+                            let mut msg = vec![0; decoder.decode_len(num_encoded_bits)];
+                            let decoded_len =
+                                decoder.decode_soft(soft, num_encoded_bits, &mut msg)?;
+                            (msg, decoded_len)
+                        }
+                    };
+                    msg.truncate(decoded_len);
+                    Ok(msg)
+                },
+            )
+            .collect()
+    }
+}
@@ -0,0 +1,451 @@
+//! Forward-backward (BCJR) decoding
+//!
+//! Computes the a-posteriori log-likelihood ratio of every input bit of a
+//! terminated frame, combining the channel LLRs of the encoded bits with
+//! optional a-priori LLRs of the input bits. All metrics are kept in the log
+//! domain, where the exact `log-MAP` sum of probabilities becomes the Jacobian
+//! logarithm and `max-log-MAP` approximates it with a plain maximum.
+
+use super::codes::Code;
+use super::trellis::Trellis;
+use super::util;
+use FecError;
+
+/// Channel LLR of a soft symbol, where 0 is a strong zero and 255 a strong one.
+/// Full-swing symbols map to about +/-4.
+pub fn soft_llr(soft: u8) -> f32 {
+    (soft as f32 - 127.5) / 32.0
+}
+
+/// How the forward-backward recursions combine competing paths
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapAlgorithm {
+    /// Keep only the best path, which is cheaper and does not depend on the
+    /// scale of the input LLRs
+    MaxLogMap,
+    /// Exact sum over all paths through the Jacobian logarithm
+    LogMap,
+}
+
+impl MapAlgorithm {
+    #[inline]
+    fn max_star(self, a: f32, b: f32) -> f32 {
+        let max = if a > b { a } else { b };
+        match self {
+            MapAlgorithm::MaxLogMap => max,
+            MapAlgorithm::LogMap => {
+                let diff = (a - b).abs();
+                if diff.is_finite() {
+                    max + (-diff).exp().ln_1p()
+                } else {
+                    max
+                }
+            }
+        }
+    }
+}
+
+/// Maximum a-posteriori decoder for terminated convolutional frames
+///
+/// LLRs are positive for a one and negative for a zero, both for the channel
+/// inputs and the a-posteriori outputs.
+#[derive(Debug, Clone)]
+pub struct MapDecoder {
+    rate: u32,
+    order: u32,
+    algorithm: MapAlgorithm,
+    trellis: Trellis,
+    output_metrics: Vec<f32>,
+    alpha: Vec<f32>,
+    beta: Vec<f32>,
+    next_beta: Vec<f32>,
+    channel: Vec<f32>,
+}
+
+impl MapDecoder {
+    /// Create a decoder, with the same supported codes as `Encoder::new`
+    pub fn new(
+        rate: u32,
+        order: u32,
+        polys: &[u16],
+        algorithm: MapAlgorithm,
+    ) -> Result<MapDecoder, FecError> {
+        util::check_code(rate, order, polys)?;
+
+        let poly_table = util::conv_poly_table(rate, order, polys);
+        let trellis = Trellis::from_poly_table(order, &poly_table);
+        Ok(MapDecoder::with_trellis(rate, order, trellis, algorithm))
+    }
+
+    /// Create a decoder for the recursive systematic code of `RscEncoder::new`
+    pub fn recursive(
+        order: u32,
+        feedback: u16,
+        polys: &[u16],
+        algorithm: MapAlgorithm,
+    ) -> Result<MapDecoder, FecError> {
+        util::check_recursive_code(order, feedback, polys)?;
+
+        let trellis = Trellis::recursive(order, feedback, polys);
+        Ok(MapDecoder::with_trellis(
+            polys.len() as u32 + 1,
+            order,
+            trellis,
+            algorithm,
+        ))
+    }
+
+    fn with_trellis(
+        rate: u32,
+        order: u32,
+        trellis: Trellis,
+        algorithm: MapAlgorithm,
+    ) -> MapDecoder {
+        MapDecoder {
+            rate,
+            order,
+            algorithm,
+            output_metrics: Vec::new(),
+            alpha: Vec::new(),
+            beta: vec![0.0; trellis.num_states],
+            next_beta: vec![0.0; trellis.num_states],
+            channel: Vec::new(),
+            trellis,
+        }
+    }
+
+    /// Create a decoder for a code from the `codes` catalog
+    pub fn from_code(code: Code, algorithm: MapAlgorithm) -> Result<MapDecoder, FecError> {
+        MapDecoder::new(code.rate, code.order, code.polys, algorithm)
+    }
+
+    /// Number of bytes written by `decode_soft` for a frame of `num_encoded_bits`
+    pub fn decode_len(&self, num_encoded_bits: usize) -> usize {
+        self.num_input_bits(num_encoded_bits) / 8
+    }
+
+    /// Number of LLRs written by `decode_llr` for a frame of `num_encoded_bits`,
+    /// which is every input bit before the tail
+    pub fn num_input_bits(&self, num_encoded_bits: usize) -> usize {
+        let num_symbols = num_encoded_bits / self.rate as usize;
+        num_symbols.saturating_sub(self.order as usize - 1)
+    }
+
+    fn check_frame(&self, num_encoded_bits: usize) -> Result<(), FecError> {
+        // the frame must at least cover the tail
+        let min_symbols = self.order as usize - 1;
+        if num_encoded_bits % self.rate as usize != 0
+            || num_encoded_bits / (self.rate as usize) < min_symbols
+        {
+            return Err(FecError::InvalidLength);
+        }
+        Ok(())
+    }
+
+    /// Fill `output_metrics` with the branch metric of every output of every symbol
+    fn branch_metrics(&mut self) {
+        let rate = self.rate as usize;
+        let num_outputs = 1 << rate;
+        self.output_metrics.clear();
+        for symbol in self.channel.chunks(rate) {
+            for output in 0..num_outputs {
+                let mut metric = 0.0;
+                for (i, llr) in symbol.iter().enumerate() {
+                    if (output >> i) & 1 == 1 {
+                        metric += 0.5 * llr;
+                    } else {
+                        metric -= 0.5 * llr;
+                    }
+                }
+                self.output_metrics.push(metric);
+            }
+        }
+    }
+
+    #[inline]
+    fn branch_metric(&self, t: usize, state: usize, bit: usize, apriori: Option<&[f32]>) -> f32 {
+        let output = self.trellis.outputs[2 * state + bit] as usize;
+        let mut metric = self.output_metrics[(t << self.rate) + output];
+        if let Some(apriori) = apriori {
+            if t < apriori.len() {
+                metric += if bit == 1 { 0.5 } else { -0.5 } * apriori[t];
+            }
+        }
+        metric
+    }
+
+    fn forward(&mut self, num_symbols: usize, apriori: Option<&[f32]>) {
+        let num_states = self.trellis.num_states;
+        self.alpha.clear();
+        self.alpha
+            .resize((num_symbols + 1) * num_states, f32::NEG_INFINITY);
+        // the encoder starts in the zero state
+        self.alpha[0] = 0.0;
+
+        for t in 0..num_symbols {
+            for state in 0..num_states {
+                let current = self.alpha[t * num_states + state];
+                if current == f32::NEG_INFINITY {
+                    continue;
+                }
+                for bit in 0..2 {
+                    let next_state = self.trellis.next_states[2 * state + bit];
+                    let metric = current + self.branch_metric(t, state, bit, apriori);
+                    let next = &mut self.alpha[(t + 1) * num_states + next_state];
+                    *next = self.algorithm.max_star(*next, metric);
+                }
+            }
+
+            // normalize so that the metrics stay small over long frames
+            let next = &mut self.alpha[(t + 1) * num_states..(t + 2) * num_states];
+            let max = next.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+            for metric in next.iter_mut() {
+                *metric -= max;
+            }
+        }
+    }
+
+    fn backward(&mut self, num_symbols: usize, apriori: Option<&[f32]>, llrs: &mut [f32]) {
+        let num_states = self.trellis.num_states;
+
+        // the tail leaves the encoder in the zero state
+        for metric in self.beta.iter_mut() {
+            *metric = f32::NEG_INFINITY;
+        }
+        self.beta[0] = 0.0;
+
+        for t in (0..num_symbols).rev() {
+            let mut ones = f32::NEG_INFINITY;
+            let mut zeros = f32::NEG_INFINITY;
+            for metric in self.next_beta.iter_mut() {
+                *metric = f32::NEG_INFINITY;
+            }
+
+            for state in 0..num_states {
+                for bit in 0..2 {
+                    let next_state = self.trellis.next_states[2 * state + bit];
+                    let branch = self.branch_metric(t, state, bit, apriori) + self.beta[next_state];
+                    self.next_beta[state] = self.algorithm.max_star(self.next_beta[state], branch);
+
+                    let path = self.alpha[t * num_states + state] + branch;
+                    if bit == 1 {
+                        ones = self.algorithm.max_star(ones, path);
+                    } else {
+                        zeros = self.algorithm.max_star(zeros, path);
+                    }
+                }
+            }
+
+            if t < llrs.len() {
+                llrs[t] = ones - zeros;
+            }
+
+            let max = self
+                .next_beta
+                .iter()
+                .cloned()
+                .fold(f32::NEG_INFINITY, f32::max);
+            for metric in self.next_beta.iter_mut() {
+                *metric -= max;
+            }
+            ::std::mem::swap(&mut self.beta, &mut self.next_beta);
+        }
+    }
+
+    fn decode_frame(&mut self, apriori: Option<&[f32]>, llrs: &mut [f32]) {
+        let num_symbols = self.channel.len() / self.rate as usize;
+        self.branch_metrics();
+
+        self.forward(num_symbols, apriori);
+        self.backward(num_symbols, apriori, llrs);
+    }
+
+    /// Decode a frame of channel LLRs, one per encoded bit, writing the
+    /// a-posteriori LLR of every input bit to `llrs`. `apriori` optionally
+    /// holds prior LLRs of the input bits, e.g. extrinsic information from
+    /// another decoder. Returns the number of LLRs written.
+    pub fn decode_llr(
+        &mut self,
+        channel: &[f32],
+        apriori: Option<&[f32]>,
+        llrs: &mut [f32],
+    ) -> Result<usize, FecError> {
+        self.check_frame(channel.len())?;
+        let num_input_bits = self.num_input_bits(channel.len());
+        if apriori.is_some_and(|apriori| apriori.len() < num_input_bits) {
+            return Err(FecError::InvalidLength);
+        }
+        if llrs.len() < num_input_bits {
+            return Err(FecError::BufferTooSmall);
+        }
+
+        self.channel.clear();
+        self.channel.extend_from_slice(channel);
+        self.decode_frame(apriori, &mut llrs[..num_input_bits]);
+        Ok(num_input_bits)
+    }
+
+    /// Decode soft symbols, one byte per encoded bit, where 0 is a strong zero
+    /// and 255 is a strong one. Writes the hard decisions to `msg` and their
+    /// LLRs to `llrs`, and returns the number of decoded bytes.
+    pub fn decode_soft(
+        &mut self,
+        soft: &[u8],
+        num_encoded_bits: usize,
+        msg: &mut [u8],
+        llrs: &mut [f32],
+    ) -> Result<usize, FecError> {
+        self.check_frame(num_encoded_bits)?;
+        if num_encoded_bits > soft.len() {
+            return Err(FecError::InvalidLength);
+        }
+        let decoded_len = self.decode_len(num_encoded_bits);
+        if msg.len() < decoded_len || llrs.len() < 8 * decoded_len {
+            return Err(FecError::BufferTooSmall);
+        }
+
+        self.channel.clear();
+        self.channel
+            .extend(soft[..num_encoded_bits].iter().map(|soft| soft_llr(*soft)));
+        self.decode_frame(None, &mut llrs[..8 * decoded_len]);
+
+        for (byte, llrs) in msg.iter_mut().zip(llrs.chunks(8)).take(decoded_len) {
+            *byte = llrs
+                .iter()
+                .fold(0, |byte, llr| (byte << 1) | (*llr > 0.0) as u8);
+        }
+        Ok(decoded_len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::codes::Code;
+    use super::super::encoder::Encoder;
+    use super::super::rsc::RscEncoder;
+    use super::{MapAlgorithm, MapDecoder};
+
+    const ALGORITHMS: [MapAlgorithm; 2] = [MapAlgorithm::MaxLogMap, MapAlgorithm::LogMap];
+
+    /// xorshift32, so that the noise is the same on every run
+    fn next_random(state: &mut u32) -> u32 {
+        *state ^= *state << 13;
+        *state ^= *state >> 17;
+        *state ^= *state << 5;
+        *state
+    }
+
+    /// Add up to `max_noise` of noise to the soft symbol of every encoded bit
+    fn noisy_soft(
+        encoded: &[u8],
+        num_encoded_bits: usize,
+        max_noise: u32,
+        random: &mut u32,
+    ) -> Vec<u8> {
+        (0..num_encoded_bits)
+            .map(|i| {
+                let bit = (encoded[i / 8] >> (7 - i % 8)) & 1;
+                let noise = (next_random(random) % max_noise) as u8;
+                if bit == 1 {
+                    255 - noise
+                } else {
+                    noise
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn noisy_round_trip() {
+        let mut random = 0x7f4a_7c15;
+        for code in &[Code::CCSDS_K7_R12, Code::NASA_K9_R13] {
+            let msg: Vec<u8> = (0..48).map(|_| next_random(&mut random) as u8).collect();
+            let mut encoder = Encoder::from_code(*code).unwrap();
+            let mut encoded = vec![0; encoder.encode_len(msg.len()).div_ceil(8)];
+            let num_encoded_bits = encoder.encode(&msg, &mut encoded).unwrap();
+            let soft = noisy_soft(&encoded, num_encoded_bits, 150, &mut random);
+
+            for algorithm in ALGORITHMS.iter() {
+                let mut decoder = MapDecoder::from_code(*code, *algorithm).unwrap();
+                let mut decoded = vec![0; decoder.decode_len(num_encoded_bits)];
+                let mut llrs = vec![0.0; 8 * decoded.len()];
+                decoder
+                    .decode_soft(&soft, num_encoded_bits, &mut decoded, &mut llrs)
+                    .unwrap();
+                assert_eq!(
+                    &decoded[..msg.len()],
+                    &msg[..],
+                    "{:?} K={}",
+                    algorithm,
+                    code.order
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn llr_signs_match_decisions() {
+        let mut random = 0x6a09_e667;
+        let code = Code::NASA_K9_R12;
+        let msg: Vec<u8> = (0..32).map(|_| next_random(&mut random) as u8).collect();
+        let mut encoder = Encoder::from_code(code).unwrap();
+        let mut encoded = vec![0; encoder.encode_len(msg.len()).div_ceil(8)];
+        let num_encoded_bits = encoder.encode(&msg, &mut encoded).unwrap();
+        let soft = noisy_soft(&encoded, num_encoded_bits, 200, &mut random);
+
+        for algorithm in ALGORITHMS.iter() {
+            let mut decoder = MapDecoder::from_code(code, *algorithm).unwrap();
+            let mut decoded = vec![0; decoder.decode_len(num_encoded_bits)];
+            let mut llrs = vec![0.0; 8 * decoded.len()];
+            decoder
+                .decode_soft(&soft, num_encoded_bits, &mut decoded, &mut llrs)
+                .unwrap();
+            for (i, llr) in llrs.iter().enumerate() {
+                let bit = (decoded[i / 8] >> (7 - i % 8)) & 1;
+                assert!(llr.is_finite());
+                assert_eq!(*llr > 0.0, bit == 1, "{:?} bit {}", algorithm, i);
+            }
+        }
+    }
+
+    #[test]
+    fn recursive_round_trip() {
+        let mut random = 0xbb67_ae85;
+        let msg: Vec<u8> = (0..16).map(|_| next_random(&mut random) as u8).collect();
+        let mut encoder = RscEncoder::new(4, 0o15, &[0o13]).unwrap();
+        let mut encoded = vec![0; encoder.encode_len(msg.len()).div_ceil(8)];
+        let num_encoded_bits = encoder.encode(&msg, &mut encoded).unwrap();
+        let soft = noisy_soft(&encoded, num_encoded_bits, 140, &mut random);
+
+        for algorithm in ALGORITHMS.iter() {
+            let mut decoder = MapDecoder::recursive(4, 0o15, &[0o13], *algorithm).unwrap();
+            assert_eq!(decoder.num_input_bits(num_encoded_bits), 8 * msg.len());
+            let mut decoded = vec![0; decoder.decode_len(num_encoded_bits)];
+            let mut llrs = vec![0.0; 8 * decoded.len()];
+            decoder
+                .decode_soft(&soft, num_encoded_bits, &mut decoded, &mut llrs)
+                .unwrap();
+            assert_eq!(&decoded[..msg.len()], &msg[..], "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn shortest_message_round_trip() {
+        for code in &[Code::GALILEO_K15_R14, Code::CASSINI_K15_R16] {
+            let mut encoder = Encoder::from_code(*code).unwrap();
+            let mut encoded = vec![0; encoder.encode_len(1).div_ceil(8)];
+            let num_encoded_bits = encoder.encode(&[0x5a], &mut encoded).unwrap();
+            let mut random = 0x3c6e_f372;
+            let soft = noisy_soft(&encoded, num_encoded_bits, 64, &mut random);
+
+            let mut decoder = MapDecoder::from_code(*code, MapAlgorithm::LogMap).unwrap();
+            let mut decoded = [0];
+            let mut llrs = [0.0; 8];
+            assert_eq!(
+                decoder.decode_soft(&soft, num_encoded_bits, &mut decoded, &mut llrs),
+                Ok(1)
+            );
+            assert_eq!(decoded, [0x5a], "K={}", code.order);
+        }
+    }
+}
@@ -18,6 +18,20 @@ impl<'a> BitWriter<'a> {
         }
     }
 
+    /// Resume writing a partial byte saved from a previous writer with `partial`
+    pub fn with_partial(
+        buf: &'a mut [u8],
+        current_byte: u8,
+        current_byte_len: usize,
+    ) -> BitWriter<'a> {
+        BitWriter {
+            buf,
+            byte_index: 0,
+            current_byte_len,
+            current_byte,
+        }
+    }
+
     pub fn write(&mut self, mut val: u8, nbits: usize) {
         for _i in 0..nbits {
             self.current_byte |= val & 1;
@@ -97,7 +111,7 @@ impl<'a> BitWriter<'a> {
 
     pub fn flush(&mut self) {
         if self.current_byte_len != 0 {
-            self.current_byte <<= 8 - self.current_byte_len;
+            self.current_byte <<= 7 - self.current_byte_len;
             self.buf[self.byte_index] = self.current_byte;
             self.byte_index += 1;
             self.current_byte_len = 0;
@@ -107,6 +121,11 @@ impl<'a> BitWriter<'a> {
     pub fn len(&self) -> usize {
         self.byte_index
     }
+
+    /// The bits written so far that have not yet filled a byte
+    pub fn partial(&self) -> (u8, usize) {
+        (self.current_byte, self.current_byte_len)
+    }
 }
 
 fn reverse_byte(b: u8) -> u8 {
@@ -144,7 +163,7 @@ impl<'a> BitReader<'a> {
             buf: buf,
             byte_index: 0,
             current_byte_len: 8,
-            current_byte: buf[0],
+            current_byte: buf.first().cloned().unwrap_or(0),
         }
     }
 
@@ -178,3 +197,30 @@ impl<'a> BitReader<'a> {
         self.buf.len()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::BitWriter;
+
+    #[test]
+    fn flush_pads_last_byte_with_zeros() {
+        let mut buf = [0xff; 2];
+        {
+            let mut bit_writer = BitWriter::new(&mut buf);
+            bit_writer.write(0xff, 8);
+            bit_writer.write(0b101, 3);
+            bit_writer.flush();
+            assert_eq!(bit_writer.len(), 2);
+        }
+        assert_eq!(buf, [0xff, 0b1010_0000]);
+
+        // a single bit ends up in the most significant bit of the byte
+        let mut buf = [0xff; 1];
+        {
+            let mut bit_writer = BitWriter::new(&mut buf);
+            bit_writer.write(1, 1);
+            bit_writer.flush();
+        }
+        assert_eq!(buf, [0x80]);
+    }
+}
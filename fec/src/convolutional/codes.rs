@@ -0,0 +1,83 @@
+//! Catalog of standard convolutional codes
+//!
+//! Polynomials follow this crate's convention, where bit 0 taps the newest bit
+//! in the shift register. Codes are usually published with the newest bit as
+//! the most significant, so e.g. the CCSDS generator 0o171 appears here
+//! reversed as 0o117.
+
+/// A rate 1/`rate` convolutional code of constraint length `order`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Code {
+    pub rate: u32,
+    pub order: u32,
+    pub polys: &'static [u16],
+}
+
+impl Code {
+    /// CCSDS/DVB K=7 rate 1/2 code, generators 171 and 133
+    pub const CCSDS_K7_R12: Code = Code {
+        rate: 2,
+        order: 7,
+        polys: &[0o117, 0o155],
+    };
+
+    /// Voyager K=7 rate 1/2 code, generators 133 and 171 in that order. libfec's
+    /// `viterbi27` sends 171 first, as `CCSDS_K7_R12` does.
+    pub const VOYAGER_K7_R12: Code = Code {
+        rate: 2,
+        order: 7,
+        polys: &[0o155, 0o117],
+    };
+
+    /// IEEE 802.11a/g K=7 rate 1/2 code, the Voyager code
+    pub const IEEE80211_K7_R12: Code = Code::VOYAGER_K7_R12;
+
+    /// LTE K=7 rate 1/3 code, generators 133, 171 and 165
+    pub const LTE_K7_R13: Code = Code {
+        rate: 3,
+        order: 7,
+        polys: &[0o155, 0o117, 0o127],
+    };
+
+    /// GSM K=5 rate 1/2 code, generators 1 + D^3 + D^4 and 1 + D + D^3 + D^4
+    pub const GSM_K5_R12: Code = Code {
+        rate: 2,
+        order: 5,
+        polys: &[0o31, 0o33],
+    };
+
+    /// NASA K=9 rate 1/2 code as used by libfec's `viterbi29`, generators 753 and 561
+    pub const NASA_K9_R12: Code = Code {
+        rate: 2,
+        order: 9,
+        polys: &[0o657, 0o435],
+    };
+
+    /// K=9 rate 1/3 code as used by libfec's `viterbi39`, generators 557, 663 and 711
+    pub const NASA_K9_R13: Code = Code {
+        rate: 3,
+        order: 9,
+        polys: &[0o755, 0o633, 0o447],
+    };
+
+    /// Galileo K=15 rate 1/4 code, generators 46321, 51271, 63667 and 70535
+    pub const GALILEO_K15_R14: Code = Code {
+        rate: 4,
+        order: 15,
+        polys: &[0o42631, 0o47245, 0o73363, 0o56507],
+    };
+
+    /// Cassini/Mars Pathfinder K=15 rate 1/6 code as used by libfec's `viterbi615`
+    pub const CASSINI_K15_R16: Code = Code {
+        rate: 6,
+        order: 15,
+        polys: &[0o42631, 0o47245, 0o56507, 0o73363, 0o77267, 0o64537],
+    };
+
+    /// libcorrect's default K=7 rate 1/2 code
+    pub const LIBCORRECT_K7_R12: Code = Code {
+        rate: 2,
+        order: 7,
+        polys: &[0o161, 0o127],
+    };
+}
@@ -1,11 +1,33 @@
+use super::acs::{self, AcsKernel};
 use super::bit::{BitReader, BitWriter};
+use super::codes::Code;
+use super::metric::{self, BranchMetric, HardMetric, PuncturedMetric, SlicedMetric, SoftMetric};
+use super::puncture::Puncture;
 use super::util;
+use FecError;
 
-use std::collections::HashMap;
+use std::cmp;
+use std::collections::{HashMap, VecDeque};
 use std::iter::Iterator;
 use std::mem;
 
-#[derive(Debug)]
+/// Reliability of a frame decoded by `Decoder::decode_with_report`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeReport {
+    /// Total branch metric of the winning path. Hard frames add one per
+    /// disagreeing bit, soft frames add the linear distance of each soft symbol.
+    pub path_metric: u32,
+    /// Number of encoded bits whose hard decision disagrees with the decoded path
+    pub corrected_errors: usize,
+    /// How much larger the metric of the other path merging into the final
+    /// state at the last step was. A competing path that merged with the
+    /// decoded one earlier can be closer still, so this only bounds the margin
+    /// to the second best path from above; a small value means the frame was
+    /// nearly decoded differently.
+    pub final_margin: u16,
+}
+
+#[derive(Debug, Clone)]
 pub struct Decoder {
     rate: u32,
     order: u32,
@@ -15,15 +37,20 @@ pub struct Decoder {
     history_table: ConvolutionalHistoryTable,
     error_table: ConvolutionalErrorTable,
     distances: Vec<u16>,
+    acs_kernel: AcsKernel,
+    branch_table: Vec<u16>,
 }
 
 impl Decoder {
-    pub fn new(rate: u32, order: u32, polys: &[u16]) -> Decoder {
+    /// Create a decoder, with the same supported codes as `Encoder::new`
+    pub fn new(rate: u32, order: u32, polys: &[u16]) -> Result<Decoder, FecError> {
+        util::check_code(rate, order, polys)?;
+
         let poly_table = util::conv_poly_table(rate, order, polys);
-        let max_error = rate * u8::max_value() as u32;
-        let renorm = u16::max_value() as u32 / max_error;
+        let max_error = rate * u8::MAX as u32;
+        let renorm = u16::MAX as u32 / max_error;
         let highbit = 1 << (order - 1);
-        Decoder {
+        Ok(Decoder {
             rate,
             order,
             highbit,
@@ -40,203 +67,757 @@ impl Decoder {
             ),
             poly_table,
             distances: vec![0; 1 << rate],
+            acs_kernel: AcsKernel::detect(highbit as usize),
+            branch_table: vec![0; (1 << rate) * acs::BLOCK_LEN],
+        })
+    }
+
+    /// Create a decoder for a code from the `codes` catalog
+    pub fn from_code(code: Code) -> Result<Decoder, FecError> {
+        Decoder::new(code.rate, code.order, code.polys)
+    }
+
+    fn warmup_step(&mut self, i: u32) {
+        {
+            let previous_errors = &self.error_table.previous_errors;
+            let errors = &mut self.error_table.errors;
+
+            // check all reg states that are up to (not including) i + 1 bits long
+            for j in 0..(1 << (i + 1)) {
+                let previous_state = j >> 1;
+
+                let distance = self.distances[self.poly_table[j] as usize];
+
+                errors[j] = distance + previous_errors[previous_state];
+            }
         }
+        self.error_table.swap();
     }
 
-    fn decode_warmup(&mut self, encoded: &mut BitReader) {
-        // XXX todo support soft
+    /// Number of warmup steps for a frame of `num_encoded_bits`, which is
+    /// `order - 1` unless the message is shorter than that
+    fn warmup_len(&self, num_encoded_bits: usize) -> u32 {
+        let num_symbols = num_encoded_bits as u32 / self.rate;
+        cmp::min(self.order - 1, num_symbols - (self.order - 1))
+    }
 
+    fn decode_warmup<M: BranchMetric>(&mut self, metric: &mut M, warmup_len: u32) {
         // we're going to prime the shift register
-        for i in 0..(self.order - 1) {
-            let outputs = encoded.read(self.rate as usize);
+        for i in 0..warmup_len {
+            metric.next_distances(&mut self.distances);
+            self.warmup_step(i);
+        }
+    }
 
+    /// After a warmup of `warmup_len` steps, cut short by a message of fewer
+    /// than `order - 1` bits, rule out the states that no message reaches, so
+    /// that the tail only follows the paths that start in the zero state
+    fn rule_out_unreached_states(&mut self, warmup_len: u32) {
+        for error in self.error_table.previous_errors[1 << warmup_len..].iter_mut() {
+            *error = u16::MAX;
+        }
+    }
+
+    /// Write out the bits still in the history at the end of a frame whose
+    /// warmup took `warmup_len` steps. After a short warmup, the tail first
+    /// shifts out the zeros the register started with, which are dropped.
+    fn flush_history(&mut self, warmup_len: u32, bit_writer: &mut BitWriter) {
+        let num_zeros = self.order - 1 - warmup_len;
+        if num_zeros == 0 {
+            self.history_table.flush(bit_writer);
+            return;
+        }
+
+        let mut tail = [0; 2];
+        {
+            let mut tail_writer = BitWriter::new(&mut tail);
+            self.history_table.flush(&mut tail_writer);
+            tail_writer.flush();
+        }
+        let mut bit_reader = BitReader::new(&tail);
+        for _ in 0..num_zeros {
+            bit_reader.read(1);
+        }
+        for _ in 0..warmup_len {
+            bit_writer.write(bit_reader.read(1), 1);
+        }
+    }
+
+    fn inner_step(&mut self, decoded: &mut BitWriter) {
+        match self.acs_kernel {
+            AcsKernel::Scalar => self.inner_step_scalar(decoded),
+            _ => self.inner_step_vector(decoded),
+        }
+    }
+
+    fn inner_step_vector(&mut self, decoded: &mut BitWriter) {
+        {
+            acs::fill_branch_table(&mut self.branch_table, &self.distances, &self.poly_table);
+            acs::add_compare_select(
+                self.acs_kernel,
+                &self.branch_table,
+                &self.poly_table,
+                &self.error_table.previous_errors,
+                &mut self.error_table.errors,
+                self.history_table.get_slice(),
+            );
+        }
+        self.history_table
+            .process(&mut self.error_table.errors, decoded);
+        self.error_table.swap();
+    }
+
+    fn inner_step_scalar(&mut self, decoded: &mut BitWriter) {
+        unsafe {
             {
+                self.pair_table.distances(&self.distances);
+                let pair_keys = &self.pair_table.keys;
+                let mut low_key_iter = pair_keys.iter();
+                let mut high_key_iter = pair_keys[(self.highbit as usize >> 1)..].iter();
+                let pair_distances = &mut self.pair_table.distances;
+
                 let previous_errors = &self.error_table.previous_errors;
-                let errors = &mut self.error_table.errors;
+                let mut errors = &mut self.error_table.errors;
+
+                let history = self.history_table.get_slice();
 
-                // check all reg states that are up to (not including) i + 1 bits long
-                for j in 0..(1 << (i + 1)) {
-                    let previous_state = j >> 1;
+                let state_iter = (0..self.highbit as usize).step_by(8);
+                let prev_state_iter = (0..(self.highbit >> 1) as usize).step_by(4);
+                let high_prev_offset = (self.highbit >> 1) as usize;
 
-                    let distance = util::metric_distance(self.poly_table[j].into(), outputs.into());
+                for (state, prev_state) in state_iter.zip(prev_state_iter) {
+                    for (state_offset, prev_offset) in (0..8).step_by(2).zip(0..4) {
+                        let low_key = *low_key_iter.next().unwrap();
+                        let high_key = *high_key_iter.next().unwrap();
 
-                    errors[j] = distance as u16 + previous_errors[previous_state];
+                        let low_concat_distance = *pair_distances.get_unchecked(low_key as usize);
+                        let high_concat_distance = *pair_distances.get_unchecked(high_key as usize);
+
+                        let low_prev_error =
+                            *previous_errors.get_unchecked(prev_state + prev_offset);
+                        let high_prev_error = *previous_errors
+                            .get_unchecked(prev_state + prev_offset + high_prev_offset);
+
+                        let low_error: u16 = (low_concat_distance & 0xffff) as u16 + low_prev_error;
+                        let high_error: u16 =
+                            (high_concat_distance & 0xffff) as u16 + high_prev_error;
+
+                        let error: u16;
+                        let successor: u8;
+                        if low_error <= high_error {
+                            error = low_error;
+                            successor = 0;
+                        } else {
+                            error = high_error;
+                            successor = 1;
+                        }
+                        *errors.get_unchecked_mut(state + state_offset) = error;
+                        *history.get_unchecked_mut(state + state_offset) = successor;
+
+                        let state = state + 1;
+
+                        let low_error = (low_concat_distance >> 16) as u16 + low_prev_error;
+                        let high_error = (high_concat_distance >> 16) as u16 + high_prev_error;
+
+                        let error: u16;
+                        let successor: u8;
+                        if low_error <= high_error {
+                            error = low_error;
+                            successor = 0;
+                        } else {
+                            error = high_error;
+                            successor = 1;
+                        }
+                        *errors.get_unchecked_mut(state + state_offset) = error;
+                        *history.get_unchecked_mut(state + state_offset) = successor;
+                    }
                 }
             }
-            self.error_table.swap();
+            self.history_table
+                .process(&mut self.error_table.errors, decoded);
         }
+        self.error_table.swap();
     }
 
-    fn decode_inner(
+    fn decode_inner<M: BranchMetric>(
         &mut self,
-        encoded: &mut BitReader,
+        metric: &mut M,
         num_encoded_bits: usize,
         decoded: &mut BitWriter,
     ) {
         // decode all bits except first (warmup) and last (tail)
         let num_decoded_bits: u32 = num_encoded_bits as u32 / self.rate;
         for _ in (self.order - 1)..(num_decoded_bits - self.order + 1) {
-            let outputs = encoded.read(self.rate as usize);
+            metric.next_distances(&mut self.distances);
+            self.inner_step(decoded);
+        }
+    }
 
-            for (j, distance) in self.distances.iter_mut().enumerate() {
-                *distance = util::metric_distance(j as u32, outputs.into()) as u16;
-            }
+    /// `remaining` counts down the tail symbols, ending at 1 for the last one.
+    /// Returns how much worse the losing path into the zero state was.
+    fn tail_step(&mut self, remaining: u32, decoded: &mut BitWriter) -> u16 {
+        let mut margin = 0;
+        {
+            let step = 1 << (self.order - remaining);
+            {
+                let previous_errors = &self.error_table.previous_errors;
+                let mut errors = &mut self.error_table.errors;
+
+                let history = self.history_table.get_slice();
+
+                let state_iter = (0..self.highbit as usize).step_by(step);
+                let prev_state_iter = (0..(self.highbit >> 1) as usize).step_by(step / 2);
+                let high_prev_offset = (self.highbit >> 1) as usize;
+
+                for (state, prev_state) in state_iter.zip(prev_state_iter) {
+                    let low_output = self.poly_table[state];
+                    let high_output = self.poly_table[state + self.highbit as usize];
+
+                    let low_prev_error = previous_errors[prev_state];
+                    let high_prev_error = previous_errors[prev_state + high_prev_offset];
+
+                    // states ruled out after a short warmup saturate
+                    let low_error =
+                        self.distances[low_output as usize].saturating_add(low_prev_error);
+                    let high_error =
+                        self.distances[high_output as usize].saturating_add(high_prev_error);
+
+                    let error: u16;
+                    let successor: u8;
+                    if low_error <= high_error {
+                        error = low_error;
+                        successor = 0;
+                    } else {
+                        error = high_error;
+                        successor = 1;
+                    }
+                    errors[state] = error;
+                    history[state] = successor;
 
-            unsafe {
-                {
-                    self.pair_table.distances(&self.distances);
-                    let pair_keys = &self.pair_table.keys;
-                    let mut low_key_iter = pair_keys.iter();
-                    let mut high_key_iter = pair_keys[(self.highbit as usize >> 1)..].iter();
-                    let pair_distances = &mut self.pair_table.distances;
-
-                    let previous_errors = &self.error_table.previous_errors;
-                    let mut errors = &mut self.error_table.errors;
-
-                    let history = self.history_table.get_slice();
-
-                    let state_iter = (0..self.highbit as usize).step_by(8);
-                    let prev_state_iter = (0..(self.highbit >> 1) as usize).step_by(4);
-                    let high_prev_offset = (self.highbit >> 1) as usize;
-
-                    for (state, prev_state) in state_iter.zip(prev_state_iter) {
-                        for (state_offset, prev_offset) in (0..8).step_by(2).zip(0..4) {
-                            let low_key = *low_key_iter.next().unwrap();
-                            let high_key = *high_key_iter.next().unwrap();
-
-                            let low_concat_distance =
-                                *pair_distances.get_unchecked(low_key as usize);
-                            let high_concat_distance =
-                                *pair_distances.get_unchecked(high_key as usize);
-
-                            let low_prev_error =
-                                *previous_errors.get_unchecked(prev_state + prev_offset);
-                            let high_prev_error = *previous_errors
-                                .get_unchecked(prev_state + prev_offset + high_prev_offset);
-
-                            let low_error: u16 =
-                                (low_concat_distance & 0xffff) as u16 + low_prev_error;
-                            let high_error: u16 =
-                                (high_concat_distance & 0xffff) as u16 + high_prev_error;
-
-                            let error: u16;
-                            let successor: u8;
-                            if low_error <= high_error {
-                                error = low_error;
-                                successor = 0;
-                            } else {
-                                error = high_error;
-                                successor = 1;
-                            }
-                            *errors.get_unchecked_mut(state + state_offset) = error;
-                            *history.get_unchecked_mut(state + state_offset) = successor;
-
-                            let state = state + 1;
-
-                            let low_error = (low_concat_distance >> 16) as u16 + low_prev_error;
-                            let high_error = (high_concat_distance >> 16) as u16 + high_prev_error;
-
-                            let error: u16;
-                            let successor: u8;
-                            if low_error <= high_error {
-                                error = low_error;
-                                successor = 0;
-                            } else {
-                                error = high_error;
-                                successor = 1;
-                            }
-                            *errors.get_unchecked_mut(state + state_offset) = error;
-                            *history.get_unchecked_mut(state + state_offset) = successor;
-                        }
+                    if state == 0 {
+                        margin = cmp::max(low_error, high_error) - error;
                     }
                 }
-                self.history_table
-                    .process(&mut self.error_table.errors, decoded);
             }
-            self.error_table.swap();
+
+            self.history_table
+                .process_step(step as u32, &mut self.error_table.errors, decoded);
         }
+        self.error_table.swap();
+        margin
     }
 
-    fn decode_tail(
+    /// Returns the margin into the zero state at the last step
+    fn decode_tail<M: BranchMetric>(
         &mut self,
-        encoded: &mut BitReader,
+        metric: &mut M,
         num_encoded_bits: usize,
         decoded: &mut BitWriter,
-    ) {
+    ) -> u16 {
         // decode last bits
         // we know that the shift register was cleared out to 0 at the end
         let num_decoded_bits: u32 = num_encoded_bits as u32 / self.rate;
+        let mut margin = 0;
         for i in (num_decoded_bits - self.order + 1)..num_decoded_bits {
-            let outputs = encoded.read(self.rate as usize);
-            for (j, distance) in self.distances.iter_mut().enumerate() {
-                *distance = util::metric_distance(j as u32, outputs.into()) as u16;
-            }
+            metric.next_distances(&mut self.distances);
+            margin = self.tail_step(num_decoded_bits - i, decoded);
+        }
+        margin
+    }
 
-            {
-                let step = 1 << (self.order - (num_decoded_bits - i));
-                {
-                    let previous_errors = &self.error_table.previous_errors;
-                    let mut errors = &mut self.error_table.errors;
+    /// Returns the margin into the zero state at the last step
+    fn decode_bits<M: BranchMetric>(
+        &mut self,
+        metric: &mut M,
+        num_encoded_bits: usize,
+        bit_writer: &mut BitWriter,
+    ) -> u16 {
+        self.error_table.reset();
+        self.history_table.reset();
 
-                    let history = self.history_table.get_slice();
+        let warmup_len = self.warmup_len(num_encoded_bits);
+        self.decode_warmup(metric, warmup_len);
+        if warmup_len < self.order - 1 {
+            self.rule_out_unreached_states(warmup_len);
+        }
+        self.decode_inner(metric, num_encoded_bits, bit_writer);
+        // a short frame leaves fewer than a traceback of history, so the tail
+        // writes nothing before the flush
+        let margin = self.decode_tail(metric, num_encoded_bits, bit_writer);
 
-                    let state_iter = (0..self.highbit as usize).step_by(step);
-                    let prev_state_iter = (0..(self.highbit >> 1) as usize).step_by(step / 2);
-                    let high_prev_offset = (self.highbit >> 1) as usize;
+        self.flush_history(warmup_len, bit_writer);
+        margin
+    }
 
-                    for (state, prev_state) in state_iter.zip(prev_state_iter) {
-                        let low_output = self.poly_table[state];
-                        let high_output = self.poly_table[state + self.highbit as usize];
+    fn decode_metric<M: BranchMetric>(
+        &mut self,
+        metric: &mut M,
+        num_encoded_bits: usize,
+        msg: &mut [u8],
+    ) -> usize {
+        let mut bit_writer = BitWriter::new(msg);
+        self.decode_bits(metric, num_encoded_bits, &mut bit_writer);
+        bit_writer.len()
+    }
 
-                        let low_prev_error = previous_errors[prev_state];
-                        let high_prev_error = previous_errors[prev_state + high_prev_offset];
+    /// Re-encode the decoded path and count the bits where `sliced`, which
+    /// yields the Hamming distance to the received hard decisions, disagrees.
+    /// `partial` holds the decoded bits that did not fill a byte of `msg`.
+    fn count_corrected<M: BranchMetric>(
+        &mut self,
+        sliced: &mut M,
+        num_encoded_bits: usize,
+        msg: &[u8],
+        partial: (u8, usize),
+    ) -> usize {
+        let num_symbols = num_encoded_bits / self.rate as usize;
+        let (partial_byte, partial_len) = partial;
+        let mut bit_reader = BitReader::new(msg);
+        let shift_mask: usize = (1 << self.order) - 1;
+        let mut shift_register: usize = 0;
+        let mut corrected = 0;
+
+        for i in 0..num_symbols {
+            // the partial byte is kept shifted up by one past its last bit
+            let bit = if i < 8 * msg.len() {
+                bit_reader.read(1)
+            } else if i < 8 * msg.len() + partial_len {
+                (partial_byte >> (8 * msg.len() + partial_len - i)) & 1
+            } else {
+                0
+            };
+            shift_register = ((shift_register << 1) | bit as usize) & shift_mask;
 
-                        let low_error = self.distances[low_output as usize] + low_prev_error;
-                        let high_error = self.distances[high_output as usize] + high_prev_error;
+            sliced.next_distances(&mut self.distances);
+            corrected += self.distances[self.poly_table[shift_register] as usize] as usize;
+        }
+        corrected
+    }
 
-                        let error: u16;
-                        let successor: u8;
-                        if low_error <= high_error {
-                            error = low_error;
-                            successor = 0;
-                        } else {
-                            error = high_error;
-                            successor = 1;
-                        }
-                        errors[state] = error;
-                        history[state] = successor;
-                    }
-                }
+    fn decode_report<M: BranchMetric, S: BranchMetric>(
+        &mut self,
+        metric: &mut M,
+        sliced: &mut S,
+        num_encoded_bits: usize,
+        msg: &mut [u8],
+    ) -> (usize, DecodeReport) {
+        let (decoded_len, partial, final_margin) = {
+            let mut bit_writer = BitWriter::new(msg);
+            let final_margin = self.decode_bits(metric, num_encoded_bits, &mut bit_writer);
+            (bit_writer.len(), bit_writer.partial(), final_margin)
+        };
+
+        // the path metric is the survivor into the zero state, plus everything
+        // renormalization took away along the way
+        let path_metric =
+            self.history_table.renormalized + self.error_table.previous_errors[0] as u32;
+        let corrected_errors =
+            self.count_corrected(sliced, num_encoded_bits, &msg[..decoded_len], partial);
+
+        let report = DecodeReport {
+            path_metric,
+            corrected_errors,
+            final_margin,
+        };
+        (decoded_len, report)
+    }
 
-                self.history_table
-                    .process_step(step as u32, &mut self.error_table.errors, decoded);
-            }
-            self.error_table.swap();
+    /// Number of bytes written by `decode` for a frame of `num_encoded_bits`
+    pub fn decode_len(&self, num_encoded_bits: usize) -> usize {
+        let num_symbols = num_encoded_bits / self.rate as usize;
+        num_symbols.saturating_sub(self.order as usize - 1) / 8
+    }
+
+    /// Number of bytes written by `decode_tail_biting` for a frame of `num_encoded_bits`
+    pub fn decode_tail_biting_len(&self, num_encoded_bits: usize) -> usize {
+        let num_symbols = num_encoded_bits / self.rate as usize;
+        num_symbols.div_ceil(8)
+    }
+
+    fn check_frame(
+        &self,
+        num_encoded_bits: usize,
+        available_bits: usize,
+        min_symbols: usize,
+    ) -> Result<(), FecError> {
+        if num_encoded_bits % self.rate as usize != 0
+            || num_encoded_bits > available_bits
+            || num_encoded_bits / (self.rate as usize) < min_symbols
+        {
+            return Err(FecError::InvalidLength);
         }
+        Ok(())
+    }
+
+    fn check_terminated_frame(
+        &self,
+        num_encoded_bits: usize,
+        available_bits: usize,
+        msg: &[u8],
+    ) -> Result<(), FecError> {
+        // the frame must at least cover the tail
+        let min_symbols = self.order as usize - 1;
+        self.check_frame(num_encoded_bits, available_bits, min_symbols)?;
+        if msg.len() < self.decode_len(num_encoded_bits) {
+            return Err(FecError::BufferTooSmall);
+        }
+        Ok(())
+    }
+
+    pub fn decode(
+        &mut self,
+        encoded: &[u8],
+        num_encoded_bits: usize,
+        msg: &mut [u8],
+    ) -> Result<usize, FecError> {
+        self.check_terminated_frame(num_encoded_bits, 8 * encoded.len(), msg)?;
+
+        let mut metric = HardMetric::new(encoded, self.rate);
+        Ok(self.decode_metric(&mut metric, num_encoded_bits, msg))
+    }
+
+    /// Decode soft symbols, one byte per encoded bit, where 0 is a strong zero
+    /// and 255 is a strong one. Returns the number of decoded bytes.
+    pub fn decode_soft(
+        &mut self,
+        soft: &[u8],
+        num_encoded_bits: usize,
+        msg: &mut [u8],
+    ) -> Result<usize, FecError> {
+        self.check_terminated_frame(num_encoded_bits, soft.len(), msg)?;
+
+        let mut metric = SoftMetric::new(&soft[..num_encoded_bits], self.rate);
+        Ok(self.decode_metric(&mut metric, num_encoded_bits, msg))
+    }
+
+    /// Like `decode`, but also report how reliable the decoded frame is
+    pub fn decode_with_report(
+        &mut self,
+        encoded: &[u8],
+        num_encoded_bits: usize,
+        msg: &mut [u8],
+    ) -> Result<(usize, DecodeReport), FecError> {
+        self.check_terminated_frame(num_encoded_bits, 8 * encoded.len(), msg)?;
+
+        let mut metric = HardMetric::new(encoded, self.rate);
+        let mut sliced = HardMetric::new(encoded, self.rate);
+        Ok(self.decode_report(&mut metric, &mut sliced, num_encoded_bits, msg))
+    }
+
+    /// Like `decode_soft`, but also report how reliable the decoded frame is
+    pub fn decode_soft_with_report(
+        &mut self,
+        soft: &[u8],
+        num_encoded_bits: usize,
+        msg: &mut [u8],
+    ) -> Result<(usize, DecodeReport), FecError> {
+        self.check_terminated_frame(num_encoded_bits, soft.len(), msg)?;
+
+        let mut metric = SoftMetric::new(&soft[..num_encoded_bits], self.rate);
+        let mut sliced = SlicedMetric::new(&soft[..num_encoded_bits], self.rate);
+        Ok(self.decode_report(&mut metric, &mut sliced, num_encoded_bits, msg))
     }
 
-    pub fn decode(&mut self, encoded: &[u8], num_encoded_bits: usize, msg: &mut [u8]) -> isize {
-        if num_encoded_bits as u32 % self.rate != 0 {
-            return -1;
+    fn decode_tail_biting_metric<M: BranchMetric>(
+        &mut self,
+        metric: &mut M,
+        num_encoded_bits: usize,
+        msg: &mut [u8],
+    ) -> usize {
+        let num_symbols = num_encoded_bits / self.rate as usize;
+        let num_outputs = self.distances.len();
+        let mut symbol_distances = vec![0; num_symbols * num_outputs];
+        for distances in symbol_distances.chunks_mut(num_outputs) {
+            metric.next_distances(distances);
         }
 
-        let mut bit_reader = BitReader::new(encoded);
+        // run the trellis around the circle, starting `wrap` symbols before the
+        // frame and stopping `wrap` symbols after it, so that the survivors have
+        // converged by the time they cover the frame itself
+        let wrap = self.history_table.min_traceback_length as usize;
+        let num_steps = num_symbols + 2 * wrap;
+        let start = num_symbols - wrap % num_symbols;
+        let mut extended = vec![0; num_steps / 8 + 1];
+        {
+            let mut bit_writer = BitWriter::new(&mut extended);
+
+            self.error_table.reset();
+            self.history_table.reset();
+
+            for step in 0..num_steps {
+                let symbol = (start + step) % num_symbols;
+                self.distances.copy_from_slice(
+                    &symbol_distances[symbol * num_outputs..(symbol + 1) * num_outputs],
+                );
+                self.inner_step(&mut bit_writer);
+            }
+
+            let best_path = self
+                .history_table
+                .least_error_path(&self.error_table.previous_errors, 1);
+            self.history_table.traceback(best_path, 0, &mut bit_writer);
+            bit_writer.flush();
+        }
+
+        // each step decodes the bit shifted in order - 1 symbols earlier
+        let offset = wrap + self.order as usize - 1;
+        let mut bit_reader = BitReader::new(&extended);
         let mut bit_writer = BitWriter::new(msg);
+        for _ in 0..offset {
+            bit_reader.read(1);
+        }
+        for _ in 0..num_symbols {
+            bit_writer.write(bit_reader.read(1), 1);
+        }
+        bit_writer.flush();
 
-        self.error_table.reset();
-        self.history_table.reset();
+        bit_writer.len()
+    }
+
+    fn check_tail_biting_frame(
+        &self,
+        num_encoded_bits: usize,
+        available_bits: usize,
+        msg: &[u8],
+    ) -> Result<(), FecError> {
+        self.check_frame(num_encoded_bits, available_bits, 0)?;
+        if msg.len() < self.decode_tail_biting_len(num_encoded_bits) {
+            return Err(FecError::BufferTooSmall);
+        }
+        Ok(())
+    }
+
+    /// Decode a tail-biting frame from `Encoder::encode_tail_biting`, which
+    /// carries no zero tail. Returns the number of decoded bytes.
+    pub fn decode_tail_biting(
+        &mut self,
+        encoded: &[u8],
+        num_encoded_bits: usize,
+        msg: &mut [u8],
+    ) -> Result<usize, FecError> {
+        self.check_tail_biting_frame(num_encoded_bits, 8 * encoded.len(), msg)?;
+        if num_encoded_bits == 0 {
+            return Ok(0);
+        }
+
+        let mut metric = HardMetric::new(encoded, self.rate);
+        Ok(self.decode_tail_biting_metric(&mut metric, num_encoded_bits, msg))
+    }
+
+    /// Decode a tail-biting frame of soft symbols, as for `decode_soft`
+    pub fn decode_tail_biting_soft(
+        &mut self,
+        soft: &[u8],
+        num_encoded_bits: usize,
+        msg: &mut [u8],
+    ) -> Result<usize, FecError> {
+        self.check_tail_biting_frame(num_encoded_bits, soft.len(), msg)?;
+        if num_encoded_bits == 0 {
+            return Ok(0);
+        }
+
+        let mut metric = SoftMetric::new(&soft[..num_encoded_bits], self.rate);
+        Ok(self.decode_tail_biting_metric(&mut metric, num_encoded_bits, msg))
+    }
+
+    /// Decode a frame from `Encoder::encode_punctured`. Returns the number of
+    /// decoded bytes.
+    pub fn decode_punctured(
+        &mut self,
+        encoded: &[u8],
+        num_encoded_bits: usize,
+        puncture: &Puncture,
+        msg: &mut [u8],
+    ) -> Result<usize, FecError> {
+        if num_encoded_bits > 8 * encoded.len() {
+            return Err(FecError::InvalidLength);
+        }
+
+        let mut soft = vec![0; num_encoded_bits];
+        if num_encoded_bits != 0 {
+            let mut bit_reader = BitReader::new(encoded);
+            for symbol in soft.iter_mut() {
+                *symbol = if bit_reader.read(1) == 1 { 255 } else { 0 };
+            }
+        }
+        self.decode_punctured_soft(&soft, num_encoded_bits, puncture, msg)
+    }
 
-        self.decode_warmup(&mut bit_reader);
-        self.decode_inner(&mut bit_reader, num_encoded_bits, &mut bit_writer);
-        self.decode_tail(&mut bit_reader, num_encoded_bits, &mut bit_writer);
+    /// Decode a punctured frame of soft symbols, as for `decode_soft`. The
+    /// dropped bits are reinserted as erasures.
+    pub fn decode_punctured_soft(
+        &mut self,
+        soft: &[u8],
+        num_encoded_bits: usize,
+        puncture: &Puncture,
+        msg: &mut [u8],
+    ) -> Result<usize, FecError> {
+        if puncture.rate() != self.rate {
+            return Err(FecError::InvalidRate);
+        }
+        if soft.len() < num_encoded_bits {
+            return Err(FecError::InvalidLength);
+        }
 
-        self.history_table.flush(&mut bit_writer);
+        let num_depunctured_bits = puncture.depunctured_len(num_encoded_bits);
+        self.check_terminated_frame(num_depunctured_bits, num_depunctured_bits, msg)?;
 
-        bit_writer.len() as isize
+        let mut metric = PuncturedMetric::new(&soft[..num_encoded_bits], puncture);
+        Ok(self.decode_metric(&mut metric, num_depunctured_bits, msg))
     }
 }
 
+/// Viterbi decoder for a continuous stream of encoded bits
+///
+/// Encoded bits may be pushed in chunks of any size. Decoded bits are written
+/// out as soon as the traceback has converged on them, and `finish` terminates
+/// the trellis on the zero tail appended by `Encoder::encode`.
 #[derive(Debug)]
+pub struct StreamingDecoder {
+    decoder: Decoder,
+    symbols: VecDeque<u8>,
+    symbol: Vec<u8>,
+    warmup_len: u32,
+    partial_byte: u8,
+    partial_len: usize,
+}
+
+impl StreamingDecoder {
+    /// Create a streaming decoder, with the same supported codes as `Encoder::new`
+    pub fn new(rate: u32, order: u32, polys: &[u16]) -> Result<StreamingDecoder, FecError> {
+        Ok(StreamingDecoder {
+            decoder: Decoder::new(rate, order, polys)?,
+            symbols: VecDeque::new(),
+            symbol: vec![0; rate as usize],
+            warmup_len: 0,
+            partial_byte: 0,
+            partial_len: 0,
+        })
+    }
+
+    /// Create a streaming decoder for a code from the `codes` catalog
+    pub fn from_code(code: Code) -> Result<StreamingDecoder, FecError> {
+        StreamingDecoder::new(code.rate, code.order, code.polys)
+    }
+
+    /// Upper bound on the bytes written by a `push` of `num_encoded_bits`
+    pub fn push_len(&self, num_encoded_bits: usize) -> usize {
+        let history_len = 20 * self.decoder.order as usize;
+        (num_encoded_bits / self.decoder.rate as usize + history_len) / 8 + 1
+    }
+
+    fn next_distances(&mut self) {
+        let rate = self.decoder.rate as usize;
+        for (dst, src) in self.symbol.iter_mut().zip(self.symbols.drain(..rate)) {
+            *dst = src;
+        }
+        metric::soft_distances(&self.symbol, &mut self.decoder.distances);
+    }
+
+    fn process(&mut self, msg: &mut [u8]) -> usize {
+        let rate = self.decoder.rate as usize;
+        let order = self.decoder.order;
+        // the last order - 1 symbols might be the tail, so hold them back until finish
+        let held = (order as usize - 1) * rate;
+
+        let mut bit_writer = BitWriter::with_partial(msg, self.partial_byte, self.partial_len);
+        while self.symbols.len() >= held + rate {
+            self.next_distances();
+            if self.warmup_len < order - 1 {
+                self.decoder.warmup_step(self.warmup_len);
+                self.warmup_len += 1;
+            } else {
+                self.decoder.inner_step(&mut bit_writer);
+            }
+        }
+
+        let (partial_byte, partial_len) = bit_writer.partial();
+        self.partial_byte = partial_byte;
+        self.partial_len = partial_len;
+        bit_writer.len()
+    }
+
+    /// Push hard bits packed into bytes, returning the number of decoded bytes
+    /// written to `msg`, which must hold at least `push_len(num_encoded_bits)`
+    pub fn push(
+        &mut self,
+        encoded: &[u8],
+        num_encoded_bits: usize,
+        msg: &mut [u8],
+    ) -> Result<usize, FecError> {
+        if num_encoded_bits > 8 * encoded.len() {
+            return Err(FecError::InvalidLength);
+        }
+        if msg.len() < self.push_len(num_encoded_bits) {
+            return Err(FecError::BufferTooSmall);
+        }
+
+        if num_encoded_bits != 0 {
+            let mut bit_reader = BitReader::new(encoded);
+            for _ in 0..num_encoded_bits {
+                let symbol = if bit_reader.read(1) == 1 { 255 } else { 0 };
+                self.symbols.push_back(symbol);
+            }
+        }
+        Ok(self.process(msg))
+    }
+
+    /// Push soft symbols, one byte per encoded bit, where 0 is a strong zero and
+    /// 255 is a strong one. Returns the number of decoded bytes written to `msg`,
+    /// which must hold at least `push_len(soft.len())`
+    pub fn push_soft(&mut self, soft: &[u8], msg: &mut [u8]) -> Result<usize, FecError> {
+        if msg.len() < self.push_len(soft.len()) {
+            return Err(FecError::BufferTooSmall);
+        }
+
+        self.symbols.extend(soft);
+        Ok(self.process(msg))
+    }
+
+    /// Decode the zero tail and write out the remaining decoded bytes, then reset
+    /// for a new stream. `msg` must hold at least `push_len(0)` bytes. Fails with
+    /// `InvalidLength`, leaving the stream as it was, if the bits pushed so far
+    /// end in a partial symbol or do not cover the tail.
+    pub fn finish(&mut self, msg: &mut [u8]) -> Result<usize, FecError> {
+        if msg.len() < self.push_len(0) {
+            return Err(FecError::BufferTooSmall);
+        }
+        let order = self.decoder.order;
+        let rate = self.decoder.rate as usize;
+        if self.symbols.len() % rate != 0 || self.symbols.len() < (order as usize - 1) * rate {
+            return Err(FecError::InvalidLength);
+        }
+        if self.warmup_len < order - 1 {
+            self.decoder.rule_out_unreached_states(self.warmup_len);
+        }
+
+        let len;
+        {
+            let mut bit_writer = BitWriter::with_partial(msg, self.partial_byte, self.partial_len);
+            for remaining in (1..order).rev() {
+                self.next_distances();
+                self.decoder.tail_step(remaining, &mut bit_writer);
+            }
+
+            self.decoder.flush_history(self.warmup_len, &mut bit_writer);
+            len = bit_writer.len();
+        }
+        self.reset();
+        Ok(len)
+    }
+
+    /// Discard any buffered bits and start a new stream
+    pub fn reset(&mut self) {
+        self.decoder.error_table.reset();
+        self.decoder.history_table.reset();
+        self.symbols.clear();
+        self.warmup_len = 0;
+        self.partial_byte = 0;
+        self.partial_len = 0;
+    }
+}
+
+#[derive(Debug, Clone)]
 struct ConvolutionalErrorTable {
     errors: Vec<u16>,
     previous_errors: Vec<u16>,
@@ -260,7 +841,7 @@ impl ConvolutionalErrorTable {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct ConvolutionalHistoryTable {
     min_traceback_length: u32,
     num_states: u32,
@@ -272,6 +853,7 @@ struct ConvolutionalHistoryTable {
     history_cap: usize,
     renormalize_interval: u32,
     renormalize_counter: u32,
+    renormalized: u32,
 }
 
 impl ConvolutionalHistoryTable {
@@ -295,6 +877,7 @@ impl ConvolutionalHistoryTable {
             history_cap: cap as usize,
             renormalize_interval,
             renormalize_counter: 0,
+            renormalized: 0,
         }
     }
 
@@ -305,7 +888,7 @@ impl ConvolutionalHistoryTable {
 
     pub fn least_error_path(&self, distances: &[u16], search_every: u32) -> u16 {
         let mut best_path: u16 = 0;
-        let mut least_error: u16 = 0;
+        let mut least_error: u16 = u16::MAX;
         for (state, distance) in distances.iter().enumerate().step_by(search_every as usize) {
             if *distance < least_error {
                 least_error = *distance;
@@ -315,8 +898,11 @@ impl ConvolutionalHistoryTable {
         best_path
     }
 
+    /// Subtract the error of `least_register` from every state, which must be
+    /// the least error of them all
     pub fn renormalize(&mut self, distances: &mut [u16], least_register: u16) {
         let min_distance = distances[least_register as usize];
+        self.renormalized = self.renormalized.wrapping_add(min_distance as u32);
         for distance in distances.iter_mut() {
             *distance -= min_distance;
         }
@@ -389,8 +975,11 @@ impl ConvolutionalHistoryTable {
 
         if self.renormalize_counter == self.renormalize_interval {
             self.renormalize_counter = 0;
+            // the tail only updates every step-th state, but the others still
+            // hold older errors that must not drop below zero
+            let least_register = self.least_error_path(distances, 1);
+            self.renormalize(distances, least_register);
             let best_path = self.least_error_path(distances, step);
-            self.renormalize(distances, best_path);
             if self.history_len == self.history_cap {
                 let min_traceback_length = self.min_traceback_length;
                 self.traceback(best_path, min_traceback_length, bit_writer);
@@ -413,10 +1002,11 @@ impl ConvolutionalHistoryTable {
     pub fn reset(&mut self) {
         self.history_len = 0;
         self.history_index = 0;
+        self.renormalized = 0;
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 /// Represent convolutional distance metrics for a pair of shift register states
 struct ConvolutionalPairTable {
     keys: Vec<u32>,
@@ -464,3 +1054,374 @@ impl ConvolutionalPairTable {
         &self.distances
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::acs::AcsKernel;
+    use super::super::codes::Code;
+    use super::super::encoder::Encoder;
+    use super::super::puncture::Puncture;
+    use super::{Decoder, StreamingDecoder};
+    use FecError;
+
+    fn available_kernels() -> Vec<AcsKernel> {
+        #[allow(unused_mut)]
+        let mut kernels = vec![AcsKernel::Scalar];
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("sse2") {
+                kernels.push(AcsKernel::Sse2);
+            }
+            if is_x86_feature_detected!("avx2") {
+                kernels.push(AcsKernel::Avx2);
+            }
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            if is_aarch64_feature_detected!("neon") {
+                kernels.push(AcsKernel::Neon);
+            }
+        }
+        kernels
+    }
+
+    /// xorshift32, so that the noise is the same on every run
+    fn next_random(state: &mut u32) -> u32 {
+        *state ^= *state << 13;
+        *state ^= *state >> 17;
+        *state ^= *state << 5;
+        *state
+    }
+
+    #[test]
+    fn kernels_agree() {
+        let mut random = 0x1234_5678;
+        for code in &[Code::CCSDS_K7_R12, Code::NASA_K9_R12, Code::CASSINI_K15_R16] {
+            let mut encoder = Encoder::from_code(*code).unwrap();
+            let msg: Vec<u8> = (0..64).map(|_| next_random(&mut random) as u8).collect();
+            let mut encoded = vec![0; encoder.encode_len(msg.len()) / 8 + 1];
+            let num_encoded_bits = encoder.encode(&msg, &mut encoded).unwrap();
+
+            let soft: Vec<u8> = (0..num_encoded_bits)
+                .map(|i| {
+                    let bit = (encoded[i / 8] >> (7 - i % 8)) & 1;
+                    let noise = (next_random(&mut random) % 192) as u8;
+                    if bit == 1 {
+                        255 - noise
+                    } else {
+                        noise
+                    }
+                })
+                .collect();
+
+            let mut expected = None;
+            for kernel in available_kernels() {
+                let mut decoder = Decoder::from_code(*code).unwrap();
+                decoder.acs_kernel = kernel;
+                let mut decoded = vec![0; decoder.decode_len(num_encoded_bits)];
+                let result = decoder
+                    .decode_soft_with_report(&soft, num_encoded_bits, &mut decoded)
+                    .unwrap();
+                match expected {
+                    None => expected = Some((decoded, result)),
+                    Some(ref expected) => assert_eq!(
+                        (&expected.0, expected.1),
+                        (&decoded, result),
+                        "{:?} on K={}",
+                        kernel,
+                        code.order
+                    ),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn streaming_matches_one_shot() {
+        let mut random = 0x9e37_79b9;
+        let code = Code::CCSDS_K7_R12;
+        let mut encoder = Encoder::from_code(code).unwrap();
+        let msg: Vec<u8> = (0..300).map(|_| next_random(&mut random) as u8).collect();
+        let mut encoded = vec![0; encoder.encode_len(msg.len()) / 8 + 1];
+        let num_encoded_bits = encoder.encode(&msg, &mut encoded).unwrap();
+        let soft: Vec<u8> = (0..num_encoded_bits)
+            .map(|i| {
+                let bit = (encoded[i / 8] >> (7 - i % 8)) & 1;
+                let noise = (next_random(&mut random) % 160) as u8;
+                if bit == 1 {
+                    255 - noise
+                } else {
+                    noise
+                }
+            })
+            .collect();
+
+        let mut decoder = Decoder::from_code(code).unwrap();
+        let mut expected = vec![0; decoder.decode_len(num_encoded_bits)];
+        decoder
+            .decode_soft(&soft, num_encoded_bits, &mut expected)
+            .unwrap();
+
+        let mut streaming = StreamingDecoder::from_code(code).unwrap();
+        for chunk_len in &[1, 7, 64, 1000] {
+            let mut decoded = Vec::new();
+            let mut buf = vec![0; streaming.push_len(*chunk_len)];
+            for chunk in soft.chunks(*chunk_len) {
+                let len = streaming.push_soft(chunk, &mut buf).unwrap();
+                decoded.extend_from_slice(&buf[..len]);
+            }
+            let len = streaming.finish(&mut buf).unwrap();
+            decoded.extend_from_slice(&buf[..len]);
+            assert_eq!(decoded, expected, "chunks of {}", chunk_len);
+        }
+    }
+
+    #[test]
+    fn streaming_rejects_short_streams() {
+        let mut streaming = StreamingDecoder::from_code(Code::CCSDS_K7_R12).unwrap();
+        let mut buf = vec![0; streaming.push_len(64)];
+
+        // a partial symbol
+        streaming.push_soft(&[0; 25], &mut buf).unwrap();
+        assert_eq!(streaming.finish(&mut buf), Err(FecError::InvalidLength));
+
+        // the tail needs 6 symbols
+        streaming.reset();
+        streaming.push_soft(&[0; 10], &mut buf).unwrap();
+        assert_eq!(streaming.finish(&mut buf), Err(FecError::InvalidLength));
+        streaming.push_soft(&[0; 2], &mut buf).unwrap();
+        assert!(streaming.finish(&mut buf).is_ok());
+    }
+
+    #[test]
+    fn shortest_messages_round_trip() {
+        for code in &[
+            Code::CCSDS_K7_R12,
+            Code::GALILEO_K15_R14,
+            Code::CASSINI_K15_R16,
+        ] {
+            for msg in [[0x00], [0xff], [0xa5], [0x01]].iter() {
+                let mut encoder = Encoder::from_code(*code).unwrap();
+                let mut encoded = vec![0; encoder.encode_len(msg.len()).div_ceil(8)];
+                let num_encoded_bits = encoder.encode(msg, &mut encoded).unwrap();
+                let soft: Vec<u8> = (0..num_encoded_bits)
+                    .map(|i| ((encoded[i / 8] >> (7 - i % 8)) & 1) * 255)
+                    .collect();
+
+                let mut decoder = Decoder::from_code(*code).unwrap();
+                let mut decoded = vec![0; decoder.decode_len(num_encoded_bits)];
+                assert_eq!(decoded.len(), 1);
+                decoder
+                    .decode(&encoded, num_encoded_bits, &mut decoded)
+                    .unwrap();
+                assert_eq!(&decoded, msg, "hard K={}", code.order);
+
+                decoded[0] = 0;
+                decoder
+                    .decode_soft(&soft, num_encoded_bits, &mut decoded)
+                    .unwrap();
+                assert_eq!(&decoded, msg, "soft K={}", code.order);
+
+                decoded[0] = 0;
+                let (_, report) = decoder
+                    .decode_with_report(&encoded, num_encoded_bits, &mut decoded)
+                    .unwrap();
+                assert_eq!(&decoded, msg, "report K={}", code.order);
+                assert_eq!(report.path_metric, 0);
+                assert_eq!(report.corrected_errors, 0);
+
+                let mut streaming = StreamingDecoder::from_code(*code).unwrap();
+                let mut buf = vec![0; streaming.push_len(num_encoded_bits)];
+                let mut len = streaming.push_soft(&soft, &mut buf).unwrap();
+                len += streaming.finish(&mut buf[len..]).unwrap();
+                assert_eq!(&buf[..len], msg, "streaming K={}", code.order);
+            }
+        }
+    }
+
+    #[test]
+    fn tail_biting_round_trip() {
+        let mut random = 0x243f_6a88;
+        for code in &[Code::CCSDS_K7_R12, Code::NASA_K9_R13] {
+            for len in &[2, 5, 40] {
+                let mut encoder = Encoder::from_code(*code).unwrap();
+                let msg: Vec<u8> = (0..*len).map(|_| next_random(&mut random) as u8).collect();
+                let mut encoded = vec![0; encoder.encode_tail_biting_len(msg.len()) / 8];
+                let num_encoded_bits = encoder.encode_tail_biting(&msg, &mut encoded).unwrap();
+                assert_eq!(num_encoded_bits, 8 * msg.len() * code.rate as usize);
+
+                // isolated errors, including one across the wrap around
+                let mut corrupted = encoded.clone();
+                for bit in (0..num_encoded_bits).step_by(40) {
+                    corrupted[bit / 8] ^= 0x80 >> (bit % 8);
+                }
+                corrupted[num_encoded_bits / 8 - 1] ^= 0x01;
+
+                let mut decoder = Decoder::from_code(*code).unwrap();
+                let mut decoded = vec![0; decoder.decode_tail_biting_len(num_encoded_bits)];
+                assert_eq!(
+                    decoder.decode_tail_biting(&corrupted, num_encoded_bits, &mut decoded),
+                    Ok(msg.len())
+                );
+                assert_eq!(decoded, msg, "hard K={} len {}", code.order, len);
+
+                let soft: Vec<u8> = (0..num_encoded_bits)
+                    .map(|i| {
+                        let bit = (encoded[i / 8] >> (7 - i % 8)) & 1;
+                        let noise = (next_random(&mut random) % 120) as u8;
+                        if bit == 1 {
+                            255 - noise
+                        } else {
+                            noise
+                        }
+                    })
+                    .collect();
+                let mut decoded = vec![0; decoder.decode_tail_biting_len(num_encoded_bits)];
+                assert_eq!(
+                    decoder.decode_tail_biting_soft(&soft, num_encoded_bits, &mut decoded),
+                    Ok(msg.len())
+                );
+                assert_eq!(decoded, msg, "soft K={} len {}", code.order, len);
+            }
+        }
+    }
+
+    #[test]
+    fn punctured_round_trip() {
+        let mut random = 0x85a3_08d3;
+        let patterns = [
+            (Code::CCSDS_K7_R12, Puncture::dvb_2_3(), 2, 3),
+            (Code::CCSDS_K7_R12, Puncture::dvb_3_4(), 3, 4),
+            (Code::CCSDS_K7_R12, Puncture::dvb_5_6(), 5, 6),
+            (Code::CCSDS_K7_R12, Puncture::dvb_7_8(), 7, 8),
+            (Code::IEEE80211_K7_R12, Puncture::ieee80211_2_3(), 2, 3),
+            (Code::IEEE80211_K7_R12, Puncture::ieee80211_3_4(), 3, 4),
+            (Code::IEEE80211_K7_R12, Puncture::ieee80211_5_6(), 5, 6),
+        ];
+        for &(code, ref puncture, k, n) in patterns.iter() {
+            // k message bits go out as n channel bits
+            assert_eq!(puncture.punctured_len(2 * k), n);
+
+            let mut encoder = Encoder::from_code(code).unwrap();
+            let msg: Vec<u8> = (0..100).map(|_| next_random(&mut random) as u8).collect();
+            let num_punctured_bits = encoder.encode_punctured_len(msg.len(), puncture);
+            let mut encoded = vec![0; num_punctured_bits.div_ceil(8)];
+            assert_eq!(
+                encoder.encode_punctured(&msg, puncture, &mut encoded),
+                Ok(num_punctured_bits)
+            );
+
+            // isolated errors, far enough apart for the weakest pattern
+            let mut corrupted = encoded.clone();
+            for bit in (0..num_punctured_bits).step_by(150) {
+                corrupted[bit / 8] ^= 0x80 >> (bit % 8);
+            }
+            let mut decoder = Decoder::from_code(code).unwrap();
+            let mut decoded = vec![0; msg.len()];
+            assert_eq!(
+                decoder.decode_punctured(&corrupted, num_punctured_bits, puncture, &mut decoded),
+                Ok(msg.len())
+            );
+            assert_eq!(decoded, msg, "hard {}/{}", k, n);
+
+            let soft: Vec<u8> = (0..num_punctured_bits)
+                .map(|i| {
+                    let bit = (encoded[i / 8] >> (7 - i % 8)) & 1;
+                    let noise = (next_random(&mut random) % 100) as u8;
+                    if bit == 1 {
+                        255 - noise
+                    } else {
+                        noise
+                    }
+                })
+                .collect();
+            let mut decoded = vec![0; msg.len()];
+            assert_eq!(
+                decoder.decode_punctured_soft(&soft, num_punctured_bits, puncture, &mut decoded),
+                Ok(msg.len())
+            );
+            assert_eq!(decoded, msg, "soft {}/{}", k, n);
+        }
+    }
+
+    #[test]
+    fn punctured_rejects_mismatched_patterns() {
+        let mut encoder = Encoder::from_code(Code::NASA_K9_R13).unwrap();
+        let mut decoder = Decoder::from_code(Code::NASA_K9_R13).unwrap();
+        let puncture = Puncture::dvb_3_4();
+        let mut buf = [0; 64];
+        assert_eq!(
+            encoder.encode_punctured(&[0; 4], &puncture, &mut buf),
+            Err(FecError::InvalidRate)
+        );
+        assert_eq!(
+            decoder.decode_punctured(&buf, 64, &puncture, &mut [0; 4]),
+            Err(FecError::InvalidRate)
+        );
+    }
+
+    #[test]
+    fn report_counts_corrected_errors() {
+        let mut random = 0x1319_8a2e;
+        let code = Code::NASA_K9_R12;
+        let mut encoder = Encoder::from_code(code).unwrap();
+        let msg: Vec<u8> = (0..100).map(|_| next_random(&mut random) as u8).collect();
+        let mut encoded = vec![0; encoder.encode_len(msg.len()).div_ceil(8)];
+        let num_encoded_bits = encoder.encode(&msg, &mut encoded).unwrap();
+
+        let mut decoder = Decoder::from_code(code).unwrap();
+        let mut decoded = vec![0; decoder.decode_len(num_encoded_bits)];
+        let (len, report) = decoder
+            .decode_with_report(&encoded, num_encoded_bits, &mut decoded)
+            .unwrap();
+        assert_eq!((len, &decoded), (msg.len(), &msg));
+        assert_eq!(report.path_metric, 0);
+        assert_eq!(report.corrected_errors, 0);
+        assert!(report.final_margin > 0);
+
+        for num_errors in 1..8 {
+            let mut corrupted = encoded.clone();
+            for i in 0..num_errors {
+                let bit = 200 * i + 37;
+                corrupted[bit / 8] ^= 0x80 >> (bit % 8);
+            }
+            let (_, report) = decoder
+                .decode_with_report(&corrupted, num_encoded_bits, &mut decoded)
+                .unwrap();
+            assert_eq!(decoded, msg);
+            assert_eq!(report.path_metric, num_errors as u32);
+            assert_eq!(report.corrected_errors, num_errors);
+            assert!(report.final_margin > 0);
+        }
+
+        // soft symbols on the right side of the threshold add their distance
+        // from the ideal symbol, without counting as errors, while those on
+        // the wrong side are also corrected errors
+        let mut noise_sum = 0;
+        let mut num_flipped = 0;
+        let soft: Vec<u8> = (0..num_encoded_bits)
+            .map(|i| {
+                let bit = (encoded[i / 8] >> (7 - i % 8)) & 1;
+                let noise = if i % 100 == 50 {
+                    num_flipped += 1;
+                    200
+                } else {
+                    (next_random(&mut random) % 64) as u8
+                };
+                noise_sum += noise as u32;
+                if bit == 1 {
+                    255 - noise
+                } else {
+                    noise
+                }
+            })
+            .collect();
+        let (_, report) = decoder
+            .decode_soft_with_report(&soft, num_encoded_bits, &mut decoded)
+            .unwrap();
+        assert_eq!(decoded, msg);
+        assert_eq!(report.path_metric, noise_sum);
+        assert_eq!(report.corrected_errors, num_flipped);
+        assert!(report.final_margin > 0);
+    }
+}
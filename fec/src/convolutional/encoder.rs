@@ -1,5 +1,8 @@
 use super::bit::{BitReader, BitWriter};
+use super::codes::Code;
+use super::puncture::Puncture;
 use super::util;
+use FecError;
 
 #[derive(Debug)]
 pub struct Encoder {
@@ -9,12 +12,23 @@ pub struct Encoder {
 }
 
 impl Encoder {
-    pub fn new(rate: u32, order: u32, polys: &[u16]) -> Encoder {
-        Encoder {
+    /// Create an encoder for the rate 1/`rate` code of constraint length `order`
+    /// with one polynomial per encoded bit. The rate must be in
+    /// `MIN_RATE..=MAX_RATE` and the order in `MIN_ORDER..=MAX_ORDER`. Each
+    /// polynomial must be nonzero and fit in `order` bits, and together they must
+    /// not form a catastrophic code.
+    pub fn new(rate: u32, order: u32, polys: &[u16]) -> Result<Encoder, FecError> {
+        util::check_code(rate, order, polys)?;
+        Ok(Encoder {
             rate: rate,
             order: order,
             poly_table: util::conv_poly_table(rate, order, polys),
-        }
+        })
+    }
+
+    /// Create an encoder for a code from the `codes` catalog
+    pub fn from_code(code: Code) -> Result<Encoder, FecError> {
+        Encoder::new(code.rate, code.order, code.polys)
     }
 
     pub fn encode_len(&self, len: usize) -> usize {
@@ -22,35 +36,233 @@ impl Encoder {
         self.rate as usize * (bits + self.order as usize + 1)
     }
 
-    pub fn encode(&mut self, msg: &[u8], dst: &mut [u8]) -> usize {
-        let mut bit_reader = BitReader::new(msg);
-        let mut bit_writer = BitWriter::new(dst);
+    fn encode_msg(&self, shift_register: &mut u32, msg: &[u8], bit_writer: &mut BitWriter) {
+        if msg.is_empty() {
+            return;
+        }
 
-        let mut shift_register: u32 = 0;
+        let mut bit_reader = BitReader::new(msg);
         let shift_mask: u32 = (1 << self.order) - 1;
-        let encode_len = self.encode_len(msg.len());
 
         for _i in 0..8 * msg.len() {
-            shift_register <<= 1;
-            shift_register |= bit_reader.read(1) as u32;
-            shift_register &= shift_mask;
+            *shift_register <<= 1;
+            *shift_register |= bit_reader.read(1) as u32;
+            *shift_register &= shift_mask;
 
             bit_writer.write(
-                self.poly_table[shift_register as usize] as u8,
+                self.poly_table[*shift_register as usize] as u8,
                 self.rate as usize,
             );
         }
+    }
+
+    fn encode_tail(&self, shift_register: &mut u32, bit_writer: &mut BitWriter) {
+        let shift_mask: u32 = (1 << self.order) - 1;
 
         for _i in 0..self.order + 1 {
-            shift_register <<= 1;
-            shift_register &= shift_mask;
+            *shift_register <<= 1;
+            *shift_register &= shift_mask;
             bit_writer.write(
-                self.poly_table[shift_register as usize] as u8,
+                self.poly_table[*shift_register as usize] as u8,
                 self.rate as usize,
             );
         }
+    }
+
+    /// Encode `msg` followed by the zero tail into `dst`, which must hold
+    /// `encode_len(msg.len())` bits. Returns the number of encoded bits.
+    pub fn encode(&mut self, msg: &[u8], dst: &mut [u8]) -> Result<usize, FecError> {
+        let encode_len = self.encode_len(msg.len());
+        if 8 * dst.len() < encode_len {
+            return Err(FecError::BufferTooSmall);
+        }
+
+        let mut bit_writer = BitWriter::new(dst);
+        let mut shift_register: u32 = 0;
+
+        self.encode_msg(&mut shift_register, msg, &mut bit_writer);
+        self.encode_tail(&mut shift_register, &mut bit_writer);
+
+        bit_writer.flush();
+        Ok(encode_len)
+    }
+
+    pub fn encode_tail_biting_len(&self, len: usize) -> usize {
+        self.rate as usize * len * 8
+    }
+
+    /// Encode `msg` without a zero tail. The shift register starts out holding
+    /// the last `order - 1` message bits, so the trellis ends in the same state
+    /// it began in. `dst` must hold `encode_tail_biting_len(msg.len())` bits.
+    pub fn encode_tail_biting(&mut self, msg: &[u8], dst: &mut [u8]) -> Result<usize, FecError> {
+        let encode_len = self.encode_tail_biting_len(msg.len());
+        if 8 * dst.len() < encode_len {
+            return Err(FecError::BufferTooSmall);
+        }
+        let num_bits = 8 * msg.len();
+        if num_bits == 0 {
+            return Ok(0);
+        }
+
+        let mut bit_writer = BitWriter::new(dst);
+
+        let mut shift_register: u32 = 0;
+        let start = num_bits - (self.order as usize - 1) % num_bits;
+        for i in 0..(self.order as usize - 1) {
+            let bit_index = (start + i) % num_bits;
+            shift_register <<= 1;
+            shift_register |= ((msg[bit_index / 8] >> (7 - bit_index % 8)) & 1) as u32;
+        }
+
+        self.encode_msg(&mut shift_register, msg, &mut bit_writer);
 
         bit_writer.flush();
-        encode_len
+        Ok(encode_len)
+    }
+
+    pub fn encode_punctured_len(&self, len: usize, puncture: &Puncture) -> usize {
+        puncture.punctured_len(self.encode_len(len))
+    }
+
+    /// Encode `msg` and transmit only the bits kept by `puncture`, which must
+    /// have one row per polynomial. Returns the number of transmitted bits.
+    pub fn encode_punctured(
+        &mut self,
+        msg: &[u8],
+        puncture: &Puncture,
+        dst: &mut [u8],
+    ) -> Result<usize, FecError> {
+        if puncture.rate() != self.rate {
+            return Err(FecError::InvalidRate);
+        }
+        if 8 * dst.len() < self.encode_punctured_len(msg.len(), puncture) {
+            return Err(FecError::BufferTooSmall);
+        }
+
+        let encode_len = self.encode_len(msg.len());
+        let mut encoded = vec![0; encode_len.div_ceil(8)];
+        self.encode(msg, &mut encoded)?;
+
+        let mut bit_reader = BitReader::new(&encoded);
+        let mut bit_writer = BitWriter::new(dst);
+        for kept in puncture.pattern().iter().cycle().take(encode_len) {
+            let bit = bit_reader.read(1);
+            if *kept {
+                bit_writer.write(bit, 1);
+            }
+        }
+
+        bit_writer.flush();
+        Ok(puncture.punctured_len(encode_len))
+    }
+}
+
+/// Convolutional encoder for a message delivered in pieces
+///
+/// The shift register is kept between calls to `push`, and the zero tail is
+/// only emitted by `finish`, so the concatenated output is identical to
+/// encoding the whole message at once with `Encoder::encode`.
+#[derive(Debug)]
+pub struct StreamingEncoder {
+    encoder: Encoder,
+    shift_register: u32,
+}
+
+impl StreamingEncoder {
+    /// Create a streaming encoder, with the same supported codes as `Encoder::new`
+    pub fn new(rate: u32, order: u32, polys: &[u16]) -> Result<StreamingEncoder, FecError> {
+        Ok(StreamingEncoder {
+            encoder: Encoder::new(rate, order, polys)?,
+            shift_register: 0,
+        })
+    }
+
+    /// Create a streaming encoder for a code from the `codes` catalog
+    pub fn from_code(code: Code) -> Result<StreamingEncoder, FecError> {
+        StreamingEncoder::new(code.rate, code.order, code.polys)
+    }
+
+    /// Number of encoded bits produced by a `push` of `len` bytes
+    pub fn push_len(&self, len: usize) -> usize {
+        self.encoder.rate as usize * len * 8
+    }
+
+    /// Number of encoded bits produced by `finish`
+    pub fn finish_len(&self) -> usize {
+        self.encoder.rate as usize * (self.encoder.order as usize + 1)
+    }
+
+    /// Encode the next piece of the message into `dst`, which must hold
+    /// `push_len(msg.len())` bits. Returns the number of encoded bits, which is
+    /// always a whole number of bytes.
+    pub fn push(&mut self, msg: &[u8], dst: &mut [u8]) -> Result<usize, FecError> {
+        let push_len = self.push_len(msg.len());
+        if 8 * dst.len() < push_len {
+            return Err(FecError::BufferTooSmall);
+        }
+
+        let mut bit_writer = BitWriter::new(dst);
+        self.encoder
+            .encode_msg(&mut self.shift_register, msg, &mut bit_writer);
+        Ok(push_len)
+    }
+
+    /// Write the zero tail that terminates the trellis into `dst`, which must
+    /// hold `finish_len()` bits, and reset for a new message. Returns the number
+    /// of encoded bits.
+    pub fn finish(&mut self, dst: &mut [u8]) -> Result<usize, FecError> {
+        let finish_len = self.finish_len();
+        if 8 * dst.len() < finish_len {
+            return Err(FecError::BufferTooSmall);
+        }
+
+        let mut bit_writer = BitWriter::new(dst);
+        self.encoder
+            .encode_tail(&mut self.shift_register, &mut bit_writer);
+        bit_writer.flush();
+        self.shift_register = 0;
+        Ok(finish_len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::codes::Code;
+    use super::{Encoder, StreamingEncoder};
+    use FecError;
+
+    #[test]
+    fn streaming_matches_one_shot() {
+        let code = Code::NASA_K9_R12;
+        let msg: Vec<u8> = (0..40).map(|i| (i * 73 + 11) as u8).collect();
+        let mut encoder = Encoder::from_code(code).unwrap();
+        let mut expected = vec![0; encoder.encode_len(msg.len()).div_ceil(8)];
+        let num_encoded_bits = encoder.encode(&msg, &mut expected).unwrap();
+
+        let mut streaming = StreamingEncoder::from_code(code).unwrap();
+        let mut encoded = Vec::new();
+        for chunk in msg.chunks(7) {
+            let mut dst = vec![0; streaming.push_len(chunk.len()) / 8];
+            let len = streaming.push(chunk, &mut dst).unwrap();
+            assert_eq!(len, 8 * dst.len());
+            encoded.extend_from_slice(&dst);
+        }
+        let mut dst = vec![0; streaming.finish_len().div_ceil(8)];
+        let len = streaming.finish(&mut dst).unwrap();
+        encoded.extend_from_slice(&dst);
+        assert_eq!(8 * msg.len() * 2 + len, num_encoded_bits);
+        assert_eq!(encoded, expected);
+    }
+
+    #[test]
+    fn streaming_rejects_small_buffers() {
+        let mut streaming = StreamingEncoder::from_code(Code::CCSDS_K7_R12).unwrap();
+        let mut dst = [0; 3];
+        assert_eq!(
+            streaming.push(&[0x12, 0x34], &mut dst),
+            Err(FecError::BufferTooSmall)
+        );
+        assert_eq!(streaming.finish(&mut [0; 1]), Err(FecError::BufferTooSmall));
+        assert_eq!(streaming.finish(&mut dst), Ok(16));
     }
 }
@@ -0,0 +1,140 @@
+use super::bit::BitReader;
+use super::puncture::Puncture;
+use super::util;
+
+use std::iter::Cycle;
+use std::slice::{Chunks, Iter};
+
+/// Source of branch metrics for the Viterbi trellis, one symbol (`rate` encoded bits) at a time
+pub trait BranchMetric {
+    /// Fill `distances[output]` with the cost of `output` having been sent as the next symbol
+    fn next_distances(&mut self, distances: &mut [u16]);
+}
+
+/// Hamming distance against hard bits packed into bytes
+#[derive(Debug)]
+pub struct HardMetric<'a> {
+    reader: BitReader<'a>,
+    rate: u32,
+}
+
+impl<'a> HardMetric<'a> {
+    pub fn new(encoded: &'a [u8], rate: u32) -> HardMetric<'a> {
+        HardMetric {
+            reader: BitReader::new(encoded),
+            rate,
+        }
+    }
+}
+
+impl<'a> BranchMetric for HardMetric<'a> {
+    fn next_distances(&mut self, distances: &mut [u16]) {
+        let outputs = self.reader.read(self.rate as usize);
+        for (j, distance) in distances.iter_mut().enumerate() {
+            *distance = util::metric_distance(j as u32, outputs.into()) as u16;
+        }
+    }
+}
+
+/// Linear distance against soft symbols, one byte per encoded bit
+/// where 0 is a strong zero and 255 is a strong one
+#[derive(Debug)]
+pub struct SoftMetric<'a> {
+    symbols: Chunks<'a, u8>,
+}
+
+impl<'a> SoftMetric<'a> {
+    pub fn new(soft: &'a [u8], rate: u32) -> SoftMetric<'a> {
+        SoftMetric {
+            symbols: soft.chunks(rate as usize),
+        }
+    }
+}
+
+impl<'a> BranchMetric for SoftMetric<'a> {
+    fn next_distances(&mut self, distances: &mut [u16]) {
+        soft_distances(self.symbols.next().unwrap(), distances);
+    }
+}
+
+/// Hamming distance against the hard decisions of soft symbols, one byte per encoded bit
+#[derive(Debug)]
+pub struct SlicedMetric<'a> {
+    symbols: Chunks<'a, u8>,
+}
+
+impl<'a> SlicedMetric<'a> {
+    pub fn new(soft: &'a [u8], rate: u32) -> SlicedMetric<'a> {
+        SlicedMetric {
+            symbols: soft.chunks(rate as usize),
+        }
+    }
+}
+
+impl<'a> BranchMetric for SlicedMetric<'a> {
+    fn next_distances(&mut self, distances: &mut [u16]) {
+        let mut outputs: u32 = 0;
+        for (i, symbol) in self.symbols.next().unwrap().iter().enumerate() {
+            if *symbol > 127 {
+                outputs |= 1 << i;
+            }
+        }
+        for (j, distance) in distances.iter_mut().enumerate() {
+            *distance = util::metric_distance(j as u32, outputs) as u16;
+        }
+    }
+}
+
+/// Fill `distances[output]` with the soft distance of each possible output from `symbols`
+pub fn soft_distances(symbols: &[u8], distances: &mut [u16]) {
+    for (j, distance) in distances.iter_mut().enumerate() {
+        *distance = util::soft_metric_distance(j as u32, symbols) as u16;
+    }
+}
+
+/// Linear distance against punctured soft symbols, where the dropped bits
+/// are treated as erasures that favour neither a zero nor a one
+#[derive(Debug)]
+pub struct PuncturedMetric<'a> {
+    soft: Iter<'a, u8>,
+    keep: Cycle<Iter<'a, bool>>,
+    symbols: Vec<u8>,
+    kept: Vec<bool>,
+}
+
+impl<'a> PuncturedMetric<'a> {
+    pub fn new(soft: &'a [u8], puncture: &'a Puncture) -> PuncturedMetric<'a> {
+        let rate = puncture.rate() as usize;
+        PuncturedMetric {
+            soft: soft.iter(),
+            keep: puncture.pattern().iter().cycle(),
+            symbols: vec![0; rate],
+            kept: vec![false; rate],
+        }
+    }
+}
+
+impl<'a> BranchMetric for PuncturedMetric<'a> {
+    fn next_distances(&mut self, distances: &mut [u16]) {
+        for (symbol, kept) in self.symbols.iter_mut().zip(self.kept.iter_mut()) {
+            *kept = false;
+            if *self.keep.next().unwrap() {
+                if let Some(soft) = self.soft.next() {
+                    *symbol = *soft;
+                    *kept = true;
+                }
+            }
+        }
+
+        for (j, distance) in distances.iter_mut().enumerate() {
+            let mut total: u32 = 0;
+            for (i, (symbol, kept)) in self.symbols.iter().zip(&self.kept).enumerate() {
+                if *kept {
+                    let expected: i32 = if (j >> i) & 1 == 1 { 255 } else { 0 };
+                    total += (*symbol as i32 - expected).unsigned_abs();
+                }
+            }
+            *distance = total as u16;
+        }
+    }
+}
@@ -1,7 +1,27 @@
-mod bit;
+mod acs;
+#[cfg(feature = "parallel")]
+mod batch;
+mod bcjr;
+pub(crate) mod bit;
+pub mod codes;
 mod decoder;
 mod encoder;
+mod metric;
+mod puncture;
+mod rsc;
+mod sova;
+mod trellis;
+mod turbo;
 mod util;
 
-pub use self::decoder::Decoder;
-pub use self::encoder::Encoder;
+#[cfg(feature = "parallel")]
+pub use self::batch::Frame;
+pub use self::bcjr::{MapAlgorithm, MapDecoder};
+pub use self::codes::Code;
+pub use self::decoder::{DecodeReport, Decoder, StreamingDecoder};
+pub use self::encoder::{Encoder, StreamingEncoder};
+pub use self::puncture::Puncture;
+pub use self::rsc::RscEncoder;
+pub use self::sova::SovaDecoder;
+pub use self::turbo::{QppInterleaver, TurboDecoder, TurboEncoder};
+pub use self::util::{MAX_ORDER, MAX_RATE, MIN_ORDER, MIN_RATE};
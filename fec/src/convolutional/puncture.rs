@@ -0,0 +1,146 @@
+use FecError;
+
+/// Puncturing pattern for deriving higher rate codes from a rate 1/N mother code
+///
+/// The pattern has one row per polynomial and one column per input bit, where
+/// a 1 marks an encoded bit that is transmitted and a 0 one that is dropped.
+/// It repeats for the length of the encoded stream.
+#[derive(Debug, Clone)]
+pub struct Puncture {
+    rate: u32,
+    keep: Vec<bool>,
+    num_kept: usize,
+}
+
+impl Puncture {
+    /// Build a pattern from its rows, e.g. `&[&[1, 0, 1], &[1, 1, 0]]` for rate 3/4.
+    /// The rows must be nonempty and of the same length, and keep at least one bit.
+    pub fn new(rows: &[&[u8]]) -> Result<Puncture, FecError> {
+        let period = rows.first().map_or(0, |row| row.len());
+        if period == 0 || rows.iter().any(|row| row.len() != period) {
+            return Err(FecError::InvalidLength);
+        }
+
+        // store the pattern in transmission order, all outputs for one input bit at a time
+        let mut keep = Vec::with_capacity(rows.len() * period);
+        for column in 0..period {
+            for row in rows {
+                keep.push(row[column] != 0);
+            }
+        }
+        let num_kept = keep.iter().filter(|kept| **kept).count();
+        if num_kept == 0 {
+            return Err(FecError::InvalidRate);
+        }
+
+        Ok(Puncture {
+            rate: rows.len() as u32,
+            keep,
+            num_kept,
+        })
+    }
+
+    /// DVB-S/DVB-T rate 2/3 for the `Code::CCSDS_K7_R12` mother code
+    pub fn dvb_2_3() -> Puncture {
+        Puncture::new(&[&[1, 0], &[1, 1]]).expect("DVB pattern is valid")
+    }
+
+    /// DVB-S/DVB-T rate 3/4 for the `Code::CCSDS_K7_R12` mother code
+    pub fn dvb_3_4() -> Puncture {
+        Puncture::new(&[&[1, 0, 1], &[1, 1, 0]]).expect("DVB pattern is valid")
+    }
+
+    /// DVB-S/DVB-T rate 5/6 for the `Code::CCSDS_K7_R12` mother code
+    pub fn dvb_5_6() -> Puncture {
+        Puncture::new(&[&[1, 0, 1, 0, 1], &[1, 1, 0, 1, 0]]).expect("DVB pattern is valid")
+    }
+
+    /// DVB-S/DVB-T rate 7/8 for the `Code::CCSDS_K7_R12` mother code
+    pub fn dvb_7_8() -> Puncture {
+        Puncture::new(&[&[1, 0, 0, 0, 1, 0, 1], &[1, 1, 1, 1, 0, 1, 0]])
+            .expect("DVB pattern is valid")
+    }
+
+    /// IEEE 802.11a/g rate 2/3 for the `Code::IEEE80211_K7_R12` mother code
+    pub fn ieee80211_2_3() -> Puncture {
+        Puncture::new(&[&[1, 1], &[1, 0]]).expect("802.11 pattern is valid")
+    }
+
+    /// IEEE 802.11a/g rate 3/4 for the `Code::IEEE80211_K7_R12` mother code
+    pub fn ieee80211_3_4() -> Puncture {
+        Puncture::new(&[&[1, 1, 0], &[1, 0, 1]]).expect("802.11 pattern is valid")
+    }
+
+    /// IEEE 802.11n rate 5/6 for the `Code::IEEE80211_K7_R12` mother code
+    pub fn ieee80211_5_6() -> Puncture {
+        Puncture::new(&[&[1, 1, 0, 1, 0], &[1, 0, 1, 0, 1]]).expect("802.11 pattern is valid")
+    }
+
+    /// Number of polynomials (rows) the pattern applies to
+    pub fn rate(&self) -> u32 {
+        self.rate
+    }
+
+    /// Whether each mother code bit is transmitted, in transmission order, for one period
+    pub fn pattern(&self) -> &[bool] {
+        &self.keep
+    }
+
+    /// Number of bits transmitted out of `num_bits` mother code bits
+    pub fn punctured_len(&self, num_bits: usize) -> usize {
+        let period = self.keep.len();
+        let remainder = self.keep[..num_bits % period]
+            .iter()
+            .filter(|kept| **kept)
+            .count();
+        (num_bits / period) * self.num_kept + remainder
+    }
+
+    /// Number of mother code bits, in whole symbols, that `num_punctured_bits` transmitted bits cover
+    pub fn depunctured_len(&self, num_punctured_bits: usize) -> usize {
+        if num_punctured_bits == 0 {
+            return 0;
+        }
+
+        let last = num_punctured_bits - 1;
+        let index = self
+            .keep
+            .iter()
+            .enumerate()
+            .filter(|&(_, kept)| *kept)
+            .nth(last % self.num_kept)
+            .map(|(index, _)| index)
+            .unwrap();
+        let num_bits = (last / self.num_kept) * self.keep.len() + index + 1;
+
+        let rate = self.rate as usize;
+        num_bits.div_ceil(rate) * rate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Puncture;
+    use FecError;
+
+    #[test]
+    fn rejects_bad_patterns() {
+        assert_eq!(Puncture::new(&[]).err(), Some(FecError::InvalidLength));
+        assert_eq!(
+            Puncture::new(&[&[], &[]]).err(),
+            Some(FecError::InvalidLength)
+        );
+        assert_eq!(
+            Puncture::new(&[&[1, 0], &[1]]).err(),
+            Some(FecError::InvalidLength)
+        );
+        assert_eq!(
+            Puncture::new(&[&[0, 0], &[0, 0]]).err(),
+            Some(FecError::InvalidRate)
+        );
+        assert_eq!(
+            Puncture::new(&[&[1, 0], &[1, 1]]).unwrap().pattern().len(),
+            4
+        );
+    }
+}
@@ -0,0 +1,71 @@
+use super::bit::{BitReader, BitWriter};
+use super::trellis::Trellis;
+use super::util;
+use FecError;
+
+/// Recursive systematic convolutional encoder
+///
+/// Each symbol holds the message bit itself followed by one parity bit per
+/// polynomial. The parity bits are computed from a shift register into which
+/// the message bit is fed back through the `feedback` polynomial, using the
+/// same tap convention as `Encoder`. Frames are terminated by `order - 1` tail
+/// bits that bring the register back to zero; unlike for feedforward codes,
+/// these depend on the register and are sent like any other message bit.
+#[derive(Debug)]
+pub struct RscEncoder {
+    rate: u32,
+    order: u32,
+    feedback: u16,
+    trellis: Trellis,
+}
+
+impl RscEncoder {
+    /// Create an encoder for the code of constraint length `order`, with rate
+    /// 1/(`polys.len()` + 1). The feedback polynomial must tap the newest bit,
+    /// and like the other polynomials fit in `order` bits.
+    pub fn new(order: u32, feedback: u16, polys: &[u16]) -> Result<RscEncoder, FecError> {
+        util::check_recursive_code(order, feedback, polys)?;
+        Ok(RscEncoder {
+            rate: polys.len() as u32 + 1,
+            order,
+            feedback,
+            trellis: Trellis::recursive(order, feedback, polys),
+        })
+    }
+
+    /// Number of encoded bits in a symbol, the message bit included
+    pub fn rate(&self) -> u32 {
+        self.rate
+    }
+
+    /// Number of encoded bits for a message of `len` bytes
+    pub fn encode_len(&self, len: usize) -> usize {
+        self.rate as usize * (8 * len + self.order as usize - 1)
+    }
+
+    /// Encode `msg` and its tail, and return the number of encoded bits
+    pub fn encode(&mut self, msg: &[u8], dst: &mut [u8]) -> Result<usize, FecError> {
+        let encode_len = self.encode_len(msg.len());
+        if 8 * dst.len() < encode_len {
+            return Err(FecError::BufferTooSmall);
+        }
+
+        let mut bit_writer = BitWriter::new(dst);
+        let mut bit_reader = BitReader::new(msg);
+        let mut state = 0;
+
+        for _ in 0..8 * msg.len() {
+            let output = self.trellis.step(&mut state, bit_reader.read(1));
+            bit_writer.write(output as u8, self.rate as usize);
+        }
+
+        for _ in 0..self.order - 1 {
+            let bit = Trellis::recursive_tail_bit(state, self.feedback);
+            let output = self.trellis.step(&mut state, bit);
+            bit_writer.write(output as u8, self.rate as usize);
+        }
+
+        bit_writer.flush();
+        Ok(encode_len)
+    }
+}
@@ -0,0 +1,330 @@
+//! Soft-output Viterbi (SOVA) decoding
+//!
+//! Runs the same add-compare-select as `Decoder` over a whole terminated
+//! frame, keeping every decision along with how close it was. After tracing
+//! back the winning path, each competing path that was discarded into it is
+//! traced back until it merges again, and every bit on which the two disagree
+//! is only as reliable as the smallest such margin (Hagenauer's rule).
+
+use super::bit::{BitReader, BitWriter};
+use super::codes::Code;
+use super::metric::{BranchMetric, SoftMetric};
+use super::util;
+use FecError;
+
+use std::cmp;
+
+/// Viterbi decoder that also returns a log-likelihood ratio for every decoded bit
+///
+/// LLRs are positive for a one and negative for a zero, in units of the soft
+/// metric: a full-swing soft symbol (or a hard bit) contributes 255. Decisions
+/// that no surviving competitor disagreed with are given `u16::MAX` in
+/// magnitude.
+///
+/// The whole trellis is kept for the length of the frame, so memory grows with
+/// the frame length times the number of states.
+#[derive(Debug, Clone)]
+pub struct SovaDecoder {
+    rate: u32,
+    order: u32,
+    num_states: usize,
+    poly_table: Vec<u16>,
+    distances: Vec<u16>,
+    errors: Vec<u32>,
+    previous_errors: Vec<u32>,
+    decisions: Vec<u8>,
+    deltas: Vec<u16>,
+    path: Vec<usize>,
+    reliability: Vec<u16>,
+}
+
+impl SovaDecoder {
+    /// Create a decoder, with the same supported codes as `Encoder::new`
+    pub fn new(rate: u32, order: u32, polys: &[u16]) -> Result<SovaDecoder, FecError> {
+        util::check_code(rate, order, polys)?;
+
+        let num_states = 1 << (order - 1);
+        Ok(SovaDecoder {
+            rate,
+            order,
+            num_states,
+            poly_table: util::conv_poly_table(rate, order, polys),
+            distances: vec![0; 1 << rate],
+            errors: vec![0; num_states],
+            previous_errors: vec![0; num_states],
+            decisions: Vec::new(),
+            deltas: Vec::new(),
+            path: Vec::new(),
+            reliability: Vec::new(),
+        })
+    }
+
+    /// Create a decoder for a code from the `codes` catalog
+    pub fn from_code(code: Code) -> Result<SovaDecoder, FecError> {
+        SovaDecoder::new(code.rate, code.order, code.polys)
+    }
+
+    /// Number of bytes written by `decode` for a frame of `num_encoded_bits`,
+    /// with one LLR per bit of those bytes
+    pub fn decode_len(&self, num_encoded_bits: usize) -> usize {
+        let num_symbols = num_encoded_bits / self.rate as usize;
+        num_symbols.saturating_sub(self.order as usize - 1) / 8
+    }
+
+    fn check_frame(
+        &self,
+        num_encoded_bits: usize,
+        available_bits: usize,
+        msg: &[u8],
+        llrs: &[f32],
+    ) -> Result<(), FecError> {
+        // the frame must at least cover the tail
+        let min_symbols = self.order as usize - 1;
+        if num_encoded_bits % self.rate as usize != 0
+            || num_encoded_bits > available_bits
+            || num_encoded_bits / (self.rate as usize) < min_symbols
+        {
+            return Err(FecError::InvalidLength);
+        }
+        let decoded_len = self.decode_len(num_encoded_bits);
+        if msg.len() < decoded_len || llrs.len() < 8 * decoded_len {
+            return Err(FecError::BufferTooSmall);
+        }
+        Ok(())
+    }
+
+    fn forward<M: BranchMetric>(&mut self, metric: &mut M, num_symbols: usize) {
+        let num_states = self.num_states;
+        let high_prev_offset = num_states / 2;
+
+        self.decisions.clear();
+        self.decisions.resize(num_symbols * num_states, 0);
+        self.deltas.clear();
+        self.deltas.resize(num_symbols * num_states, 0);
+
+        // the encoder starts in the zero state, so every other state starts out
+        // far enough behind to never be chosen
+        for error in self.previous_errors.iter_mut() {
+            *error = u32::MAX / 2;
+        }
+        self.previous_errors[0] = 0;
+
+        for t in 0..num_symbols {
+            metric.next_distances(&mut self.distances);
+
+            let decisions = &mut self.decisions[t * num_states..(t + 1) * num_states];
+            let deltas = &mut self.deltas[t * num_states..(t + 1) * num_states];
+            let mut least_error = u32::MAX;
+            for state in 0..num_states {
+                let low_output = self.poly_table[state];
+                let high_output = self.poly_table[state + num_states];
+
+                let low_error =
+                    self.previous_errors[state / 2] + self.distances[low_output as usize] as u32;
+                let high_error = self.previous_errors[state / 2 + high_prev_offset]
+                    + self.distances[high_output as usize] as u32;
+
+                let error = cmp::min(low_error, high_error);
+                let delta = cmp::max(low_error, high_error) - error;
+                self.errors[state] = error;
+                decisions[state] = (low_error > high_error) as u8;
+                deltas[state] = cmp::min(delta, u16::MAX as u32) as u16;
+                least_error = cmp::min(least_error, error);
+            }
+
+            // keep the metrics small, leaving unreachable states saturated
+            for error in self.errors.iter_mut() {
+                *error = cmp::min(*error - least_error, u32::MAX / 2);
+            }
+            ::std::mem::swap(&mut self.errors, &mut self.previous_errors);
+        }
+    }
+
+    fn predecessor(&self, state: usize, decision: u8) -> usize {
+        (state >> 1) | (decision as usize * self.num_states / 2)
+    }
+
+    fn backward(&mut self, num_symbols: usize) {
+        let num_states = self.num_states;
+
+        // trace the winning path back from the zero state the tail leaves behind
+        self.path.clear();
+        self.path.resize(num_symbols, 0);
+        let mut state = 0;
+        for t in (0..num_symbols).rev() {
+            self.path[t] = state;
+            let decision = self.decisions[t * num_states + state];
+            state = self.predecessor(state, decision);
+        }
+
+        self.reliability.clear();
+        self.reliability.resize(num_symbols, u16::MAX);
+        for t in 1..num_symbols {
+            let state = self.path[t];
+            let delta = self.deltas[t * num_states + state];
+            let decision = self.decisions[t * num_states + state];
+
+            // follow the path that lost at this step until it rejoins the winner
+            let mut competitor = self.predecessor(state, decision ^ 1);
+            for j in (0..t).rev() {
+                if competitor == self.path[j] {
+                    break;
+                }
+                if (competitor ^ self.path[j]) & 1 == 1 {
+                    self.reliability[j] = cmp::min(self.reliability[j], delta);
+                }
+                let decision = self.decisions[j * num_states + competitor];
+                competitor = self.predecessor(competitor, decision);
+            }
+        }
+    }
+
+    fn decode_metric<M: BranchMetric>(
+        &mut self,
+        metric: &mut M,
+        num_encoded_bits: usize,
+        msg: &mut [u8],
+        llrs: &mut [f32],
+    ) -> usize {
+        let num_symbols = num_encoded_bits / self.rate as usize;
+        self.forward(metric, num_symbols);
+        self.backward(num_symbols);
+
+        let decoded_len = self.decode_len(num_encoded_bits);
+        let mut bit_writer = BitWriter::new(msg);
+        for (t, llr) in llrs.iter_mut().enumerate().take(8 * decoded_len) {
+            let bit = (self.path[t] & 1) as u8;
+            let reliability = self.reliability[t] as f32;
+            *llr = if bit == 1 { reliability } else { -reliability };
+            bit_writer.write(bit, 1);
+        }
+        decoded_len
+    }
+
+    /// Decode packed hard bits, writing the decoded bytes to `msg` and one LLR
+    /// per decoded bit to `llrs`. Returns the number of decoded bytes.
+    pub fn decode(
+        &mut self,
+        encoded: &[u8],
+        num_encoded_bits: usize,
+        msg: &mut [u8],
+        llrs: &mut [f32],
+    ) -> Result<usize, FecError> {
+        self.check_frame(num_encoded_bits, 8 * encoded.len(), msg, llrs)?;
+
+        // hard bits are full-swing soft symbols, which keeps the LLRs on one scale
+        let mut bit_reader = BitReader::new(encoded);
+        let soft: Vec<u8> = (0..num_encoded_bits)
+            .map(|_| bit_reader.read(1) * u8::MAX)
+            .collect();
+        let mut metric = SoftMetric::new(&soft, self.rate);
+        Ok(self.decode_metric(&mut metric, num_encoded_bits, msg, llrs))
+    }
+
+    /// Decode soft symbols, one byte per encoded bit, where 0 is a strong zero
+    /// and 255 is a strong one. Returns the number of decoded bytes.
+    pub fn decode_soft(
+        &mut self,
+        soft: &[u8],
+        num_encoded_bits: usize,
+        msg: &mut [u8],
+        llrs: &mut [f32],
+    ) -> Result<usize, FecError> {
+        self.check_frame(num_encoded_bits, soft.len(), msg, llrs)?;
+
+        let mut metric = SoftMetric::new(&soft[..num_encoded_bits], self.rate);
+        Ok(self.decode_metric(&mut metric, num_encoded_bits, msg, llrs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::codes::Code;
+    use super::super::decoder::Decoder;
+    use super::super::encoder::Encoder;
+    use super::SovaDecoder;
+
+    /// xorshift32, so that the noise is the same on every run
+    fn next_random(state: &mut u32) -> u32 {
+        *state ^= *state << 13;
+        *state ^= *state >> 17;
+        *state ^= *state << 5;
+        *state
+    }
+
+    /// Encode `msg` and add up to `max_noise` of noise to every soft symbol
+    fn noisy_frame(code: Code, msg: &[u8], max_noise: u32, random: &mut u32) -> Vec<u8> {
+        let mut encoder = Encoder::from_code(code).unwrap();
+        let mut encoded = vec![0; encoder.encode_len(msg.len()).div_ceil(8)];
+        let num_encoded_bits = encoder.encode(msg, &mut encoded).unwrap();
+        (0..num_encoded_bits)
+            .map(|i| {
+                let bit = (encoded[i / 8] >> (7 - i % 8)) & 1;
+                let noise = (next_random(random) % max_noise) as u8;
+                if bit == 1 {
+                    255 - noise
+                } else {
+                    noise
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_viterbi_on_noisy_frames() {
+        let mut random = 0x2545_f491;
+        for code in &[Code::CCSDS_K7_R12, Code::NASA_K9_R13] {
+            let msg: Vec<u8> = (0..64).map(|_| next_random(&mut random) as u8).collect();
+            let soft = noisy_frame(*code, &msg, 150, &mut random);
+
+            let mut viterbi = Decoder::from_code(*code).unwrap();
+            let mut expected = vec![0; viterbi.decode_len(soft.len())];
+            viterbi
+                .decode_soft(&soft, soft.len(), &mut expected)
+                .unwrap();
+            assert_eq!(expected, msg);
+
+            let mut sova = SovaDecoder::from_code(*code).unwrap();
+            let mut decoded = vec![0; sova.decode_len(soft.len())];
+            let mut llrs = vec![0.0; 8 * decoded.len()];
+            sova.decode_soft(&soft, soft.len(), &mut decoded, &mut llrs)
+                .unwrap();
+            assert_eq!(decoded, expected, "K={}", code.order);
+        }
+    }
+
+    #[test]
+    fn llr_signs_match_decisions() {
+        let mut random = 0x0bad_5eed;
+        let code = Code::CCSDS_K7_R12;
+        let msg: Vec<u8> = (0..32).map(|_| next_random(&mut random) as u8).collect();
+        let soft = noisy_frame(code, &msg, 200, &mut random);
+
+        let mut sova = SovaDecoder::from_code(code).unwrap();
+        let mut decoded = vec![0; sova.decode_len(soft.len())];
+        let mut llrs = vec![0.0; 8 * decoded.len()];
+        sova.decode_soft(&soft, soft.len(), &mut decoded, &mut llrs)
+            .unwrap();
+        for (i, llr) in llrs.iter().enumerate() {
+            let bit = (decoded[i / 8] >> (7 - i % 8)) & 1;
+            assert!(*llr != 0.0);
+            assert_eq!(*llr > 0.0, bit == 1, "bit {}", i);
+        }
+    }
+
+    #[test]
+    fn shortest_message_round_trip() {
+        let mut random = 0x1357_9bdf;
+        for code in &[Code::GALILEO_K15_R14, Code::CASSINI_K15_R16] {
+            let soft = noisy_frame(*code, &[0xc3], 64, &mut random);
+            let mut sova = SovaDecoder::from_code(*code).unwrap();
+            let mut decoded = [0];
+            let mut llrs = [0.0; 8];
+            assert_eq!(
+                sova.decode_soft(&soft, soft.len(), &mut decoded, &mut llrs),
+                Ok(1)
+            );
+            assert_eq!(decoded, [0xc3], "K={}", code.order);
+        }
+    }
+}
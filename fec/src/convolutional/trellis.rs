@@ -0,0 +1,76 @@
+/// Transitions of a trellis, one per state and input bit
+///
+/// A state holds the `order - 1` most recent bits of the shift register, newest
+/// in bit 0, and `outputs` packs the encoded bits of a transition in the order
+/// they are transmitted, starting from bit 0.
+#[derive(Debug, Clone)]
+pub struct Trellis {
+    pub num_states: usize,
+    pub next_states: Vec<usize>,
+    pub outputs: Vec<u16>,
+}
+
+fn parity(x: usize) -> usize {
+    x.count_ones() as usize & 1
+}
+
+impl Trellis {
+    /// Feedforward trellis from the encoder's table of outputs per shift register
+    pub fn from_poly_table(order: u32, poly_table: &[u16]) -> Trellis {
+        let num_states = 1 << (order - 1);
+        let mut next_states = Vec::with_capacity(2 * num_states);
+        let mut outputs = Vec::with_capacity(2 * num_states);
+        for state in 0..num_states {
+            for bit in 0..2 {
+                let shift_register = (state << 1) | bit;
+                next_states.push(shift_register & (num_states - 1));
+                outputs.push(poly_table[shift_register]);
+            }
+        }
+        Trellis {
+            num_states,
+            next_states,
+            outputs,
+        }
+    }
+
+    /// Recursive systematic trellis, where the bit shifted into the register is
+    /// the input xored with the `feedback` taps of the older bits. Each output
+    /// starts with the input bit itself, followed by one parity bit per polynomial.
+    pub fn recursive(order: u32, feedback: u16, polys: &[u16]) -> Trellis {
+        let num_states = 1 << (order - 1);
+        let mut next_states = Vec::with_capacity(2 * num_states);
+        let mut outputs = Vec::with_capacity(2 * num_states);
+        for state in 0..num_states {
+            for bit in 0..2 {
+                let fed_back = bit ^ parity((state << 1) & feedback as usize);
+                let shift_register = (state << 1) | fed_back;
+                next_states.push(shift_register & (num_states - 1));
+
+                let mut output = bit as u16;
+                for (i, poly) in polys.iter().enumerate() {
+                    output |= (parity(shift_register & *poly as usize) as u16) << (i + 1);
+                }
+                outputs.push(output);
+            }
+        }
+        Trellis {
+            num_states,
+            next_states,
+            outputs,
+        }
+    }
+
+    /// Follow the transition for `bit` out of `state`, returning its output
+    pub fn step(&self, state: &mut usize, bit: u8) -> u16 {
+        let transition = 2 * *state + bit as usize;
+        *state = self.next_states[transition];
+        self.outputs[transition]
+    }
+
+    /// Input bit that shifts a zero into a recursive register, which is what
+    /// drives it back to the zero state when terminating
+    pub fn recursive_tail_bit(state: usize, feedback: u16) -> u8 {
+        parity((state << 1) & feedback as usize) as u8
+    }
+}
@@ -0,0 +1,482 @@
+//! Parallel concatenated (turbo) codes
+//!
+//! Two recursive systematic encoders see the same message, the second one
+//! through an interleaver, and only the first sends the message bits. The
+//! decoder runs a MAP decoder for each of them in turn, each passing the other
+//! the extrinsic information it gained about the message bits as a-priori
+//! LLRs, until the decisions settle.
+//!
+//! For a message of `K` bits an encoded frame holds, in order:
+//!
+//! * `K` symbols of the message bit, the first parity bit and the second parity bit
+//! * `order - 1` tail symbols of the first encoder, its tail bit then its parity bit
+//! * `order - 1` tail symbols of the second encoder, in the same layout
+
+use super::bcjr::{self, MapAlgorithm, MapDecoder};
+use super::bit::{BitReader, BitWriter};
+use super::trellis::Trellis;
+use super::util;
+use interleave::Interleaver;
+use FecError;
+
+/// Order of the LTE turbo code constituents
+const LTE_ORDER: u32 = 4;
+/// LTE feedback polynomial 1 + D^2 + D^3
+const LTE_FEEDBACK: u16 = 0o15;
+/// LTE parity polynomial 1 + D + D^3
+const LTE_POLY: u16 = 0o13;
+
+/// Quadratic permutation polynomial interleaver, as used by LTE
+///
+/// Output bit `i` is input bit `(f1 * i + f2 * i * i) % len`. The LTE
+/// parameters for each block size are listed in 3GPP TS 36.212 table 5.1.3-3,
+/// e.g. `f1 = 3, f2 = 10` for 40 bits and `f1 = 263, f2 = 480` for 6144 bits.
+#[derive(Debug, Clone)]
+pub struct QppInterleaver {
+    interleaver: Interleaver,
+}
+
+impl QppInterleaver {
+    /// Create an interleaver of `len` bits, which must be a nonzero number of
+    /// whole bytes and for which `f1` and `f2` must give a permutation
+    pub fn new(len: usize, f1: usize, f2: usize) -> Result<QppInterleaver, FecError> {
+        if len == 0 || len % 8 != 0 {
+            return Err(FecError::InvalidLength);
+        }
+
+        let permutation = (0..len as u64)
+            .map(|i| ((f1 as u64 * i + f2 as u64 * i * i) % len as u64) as usize)
+            .collect();
+        let interleaver = Interleaver::from_permutation(permutation)?;
+        Ok(QppInterleaver { interleaver })
+    }
+
+    /// Number of bits interleaved, which is the message length of the turbo code
+    pub fn num_bits(&self) -> usize {
+        self.interleaver.num_symbols()
+    }
+
+    /// Input bit index for every output bit
+    pub fn permutation(&self) -> &[usize] {
+        self.interleaver.permutation()
+    }
+
+    /// The interleaver as a block `Interleaver`, e.g. to interleave LLRs
+    pub fn interleaver(&self) -> &Interleaver {
+        &self.interleaver
+    }
+}
+
+#[derive(Debug)]
+pub struct TurboEncoder {
+    order: u32,
+    feedback: u16,
+    trellis: Trellis,
+    interleaver: QppInterleaver,
+}
+
+impl TurboEncoder {
+    /// Create a rate 1/3 turbo encoder from two copies of the recursive
+    /// systematic code of `RscEncoder::new` with a single parity polynomial
+    pub fn new(
+        order: u32,
+        feedback: u16,
+        poly: u16,
+        interleaver: QppInterleaver,
+    ) -> Result<TurboEncoder, FecError> {
+        util::check_recursive_code(order, feedback, &[poly])?;
+        Ok(TurboEncoder {
+            order,
+            feedback,
+            trellis: Trellis::recursive(order, feedback, &[poly]),
+            interleaver,
+        })
+    }
+
+    /// Create an encoder with the LTE constituent code, feedback polynomial
+    /// 1 + D^2 + D^3 and parity polynomial 1 + D + D^3
+    pub fn lte(interleaver: QppInterleaver) -> Result<TurboEncoder, FecError> {
+        TurboEncoder::new(LTE_ORDER, LTE_FEEDBACK, LTE_POLY, interleaver)
+    }
+
+    /// Length in bytes of the messages this encoder takes
+    pub fn msg_len(&self) -> usize {
+        self.interleaver.num_bits() / 8
+    }
+
+    /// Number of encoded bits in a frame
+    pub fn encode_len(&self) -> usize {
+        3 * self.interleaver.num_bits() + 4 * (self.order as usize - 1)
+    }
+
+    fn encode_tail(&self, state: &mut usize, bit_writer: &mut BitWriter) {
+        for _ in 0..self.order - 1 {
+            let bit = Trellis::recursive_tail_bit(*state, self.feedback);
+            let output = self.trellis.step(state, bit);
+            bit_writer.write(output as u8, 2);
+        }
+    }
+
+    /// Encode `msg`, which must be `msg_len` bytes long, and return the number
+    /// of encoded bits
+    pub fn encode(&mut self, msg: &[u8], dst: &mut [u8]) -> Result<usize, FecError> {
+        if msg.len() != self.msg_len() {
+            return Err(FecError::InvalidLength);
+        }
+        let encode_len = self.encode_len();
+        if 8 * dst.len() < encode_len {
+            return Err(FecError::BufferTooSmall);
+        }
+
+        let mut bits = vec![0; self.interleaver.num_bits()];
+        let mut bit_reader = BitReader::new(msg);
+        for bit in bits.iter_mut() {
+            *bit = bit_reader.read(1);
+        }
+
+        let mut bit_writer = BitWriter::new(dst);
+        let mut state = 0;
+        let mut interleaved_state = 0;
+        for (bit, index) in bits.iter().zip(self.interleaver.permutation()) {
+            let output = self.trellis.step(&mut state, *bit);
+            let interleaved_output = self.trellis.step(&mut interleaved_state, bits[*index]);
+            bit_writer.write(*bit, 1);
+            bit_writer.write((output >> 1) as u8, 1);
+            bit_writer.write((interleaved_output >> 1) as u8, 1);
+        }
+
+        self.encode_tail(&mut state, &mut bit_writer);
+        self.encode_tail(&mut interleaved_state, &mut bit_writer);
+
+        bit_writer.flush();
+        Ok(encode_len)
+    }
+}
+
+/// Iterative decoder for the frames of `TurboEncoder`
+///
+/// LLRs are positive for a one and negative for a zero, as for `MapDecoder`.
+#[derive(Debug, Clone)]
+pub struct TurboDecoder {
+    order: u32,
+    interleaver: QppInterleaver,
+    max_iterations: usize,
+    decoder: MapDecoder,
+    channel: Vec<f32>,
+    interleaved_channel: Vec<f32>,
+    apriori: Vec<f32>,
+    interleaved_apriori: Vec<f32>,
+    llrs: Vec<f32>,
+    decisions: Vec<bool>,
+}
+
+impl TurboDecoder {
+    /// Create a decoder for `TurboEncoder::new` that runs at most
+    /// `max_iterations` rounds of both MAP decoders
+    pub fn new(
+        order: u32,
+        feedback: u16,
+        poly: u16,
+        interleaver: QppInterleaver,
+        algorithm: MapAlgorithm,
+        max_iterations: usize,
+    ) -> Result<TurboDecoder, FecError> {
+        let decoder = MapDecoder::recursive(order, feedback, &[poly], algorithm)?;
+        let len = interleaver.num_bits();
+        let num_symbols = len + order as usize - 1;
+        Ok(TurboDecoder {
+            order,
+            interleaver,
+            max_iterations,
+            decoder,
+            channel: vec![0.0; 2 * num_symbols],
+            interleaved_channel: vec![0.0; 2 * num_symbols],
+            apriori: vec![0.0; len],
+            interleaved_apriori: vec![0.0; len],
+            llrs: vec![0.0; len],
+            decisions: vec![false; len],
+        })
+    }
+
+    /// Create a decoder for `TurboEncoder::lte`
+    pub fn lte(
+        interleaver: QppInterleaver,
+        algorithm: MapAlgorithm,
+        max_iterations: usize,
+    ) -> Result<TurboDecoder, FecError> {
+        TurboDecoder::new(
+            LTE_ORDER,
+            LTE_FEEDBACK,
+            LTE_POLY,
+            interleaver,
+            algorithm,
+            max_iterations,
+        )
+    }
+
+    /// Length in bytes of the decoded messages
+    pub fn msg_len(&self) -> usize {
+        self.interleaver.num_bits() / 8
+    }
+
+    /// Number of encoded bits in a frame
+    pub fn encode_len(&self) -> usize {
+        3 * self.interleaver.num_bits() + 4 * (self.order as usize - 1)
+    }
+
+    /// Split a frame into the channel LLRs seen by each constituent decoder
+    fn demultiplex(&mut self, channel: &[f32]) {
+        let len = self.interleaver.num_bits();
+        let tail_len = 2 * (self.order as usize - 1);
+        let (symbols, tails) = channel.split_at(3 * len);
+        let (tail, interleaved_tail) = tails.split_at(tail_len);
+
+        for (k, index) in self.interleaver.permutation().iter().enumerate() {
+            self.channel[2 * k] = symbols[3 * k];
+            self.channel[2 * k + 1] = symbols[3 * k + 1];
+            self.interleaved_channel[2 * k] = symbols[3 * index];
+            self.interleaved_channel[2 * k + 1] = symbols[3 * k + 2];
+        }
+        self.channel[2 * len..].copy_from_slice(tail);
+        self.interleaved_channel[2 * len..].copy_from_slice(interleaved_tail);
+    }
+
+    /// Run one constituent decoder and turn its a-posteriori LLRs into
+    /// extrinsic information, in place in `llrs`
+    fn decode_constituent(&mut self, interleaved: bool) {
+        let (channel, apriori) = if interleaved {
+            (&self.interleaved_channel, &self.interleaved_apriori)
+        } else {
+            (&self.channel, &self.apriori)
+        };
+        self.decoder
+            .decode_llr(channel, Some(apriori), &mut self.llrs)
+            .expect("constituent frames are sized by the turbo decoder");
+
+        // leave out what the decoder was told, so that it is not counted twice
+        for (k, llr) in self.llrs.iter_mut().enumerate() {
+            *llr -= apriori[k] + channel[2 * k];
+        }
+    }
+
+    /// Decode a frame of channel LLRs, one per encoded bit, writing the
+    /// a-posteriori LLR of every message bit to `llrs`. Stops early once an
+    /// iteration leaves every decision unchanged. Returns the number of
+    /// iterations run.
+    pub fn decode_llr(&mut self, channel: &[f32], llrs: &mut [f32]) -> Result<usize, FecError> {
+        let len = self.interleaver.num_bits();
+        if channel.len() != self.encode_len() {
+            return Err(FecError::InvalidLength);
+        }
+        if llrs.len() < len {
+            return Err(FecError::BufferTooSmall);
+        }
+
+        self.demultiplex(channel);
+        for apriori in self.apriori.iter_mut() {
+            *apriori = 0.0;
+        }
+
+        let mut iterations = 0;
+        while iterations < self.max_iterations {
+            iterations += 1;
+
+            self.decode_constituent(false);
+            self.interleaver
+                .interleaver()
+                .interleave(&self.llrs, &mut self.interleaved_apriori)
+                .expect("LLRs are sized by the turbo decoder");
+
+            self.decode_constituent(true);
+            let mut changed = false;
+            for (k, index) in self.interleaver.permutation().iter().enumerate() {
+                let extrinsic = self.llrs[k];
+                let llr = extrinsic + self.interleaved_apriori[k] + self.interleaved_channel[2 * k];
+                llrs[*index] = llr;
+
+                let decision = llr > 0.0;
+                changed |= decision != self.decisions[*index];
+                self.decisions[*index] = decision;
+
+                self.apriori[*index] = extrinsic;
+            }
+
+            if !changed && iterations > 1 {
+                break;
+            }
+        }
+        Ok(iterations)
+    }
+
+    /// Decode soft symbols, one byte per encoded bit, where 0 is a strong zero
+    /// and 255 is a strong one. Returns the number of decoded bytes.
+    pub fn decode_soft(&mut self, soft: &[u8], msg: &mut [u8]) -> Result<usize, FecError> {
+        if soft.len() != self.encode_len() {
+            return Err(FecError::InvalidLength);
+        }
+        let msg_len = self.msg_len();
+        if msg.len() < msg_len {
+            return Err(FecError::BufferTooSmall);
+        }
+
+        let channel: Vec<f32> = soft.iter().map(|soft| bcjr::soft_llr(*soft)).collect();
+        let mut llrs = vec![0.0; self.interleaver.num_bits()];
+        self.decode_llr(&channel, &mut llrs)?;
+
+        for (byte, llrs) in msg.iter_mut().zip(llrs.chunks(8)).take(msg_len) {
+            *byte = llrs
+                .iter()
+                .fold(0, |byte, llr| (byte << 1) | (*llr > 0.0) as u8);
+        }
+        Ok(msg_len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::bcjr::MapAlgorithm;
+    use super::super::rsc::RscEncoder;
+    use super::{QppInterleaver, TurboDecoder, TurboEncoder};
+    use FecError;
+
+    /// xorshift32, so that the noise is the same on every run
+    fn next_random(state: &mut u32) -> u32 {
+        *state ^= *state << 13;
+        *state ^= *state >> 17;
+        *state ^= *state << 5;
+        *state
+    }
+
+    /// Roughly Gaussian noise of unit variance, from the sum of 12 uniforms
+    fn next_gaussian(state: &mut u32) -> f32 {
+        let sum: f32 = (0..12)
+            .map(|_| next_random(state) as f32 / u32::MAX as f32)
+            .sum();
+        sum - 6.0
+    }
+
+    /// Encode a random message of `len` bits and send it over a BPSK channel
+    /// with noise of deviation `sigma`, returning the message and channel LLRs
+    fn noisy_frame(
+        len: usize,
+        f1: usize,
+        f2: usize,
+        sigma: f32,
+        random: &mut u32,
+    ) -> (Vec<u8>, Vec<f32>) {
+        let mut encoder = TurboEncoder::lte(QppInterleaver::new(len, f1, f2).unwrap()).unwrap();
+        let msg: Vec<u8> = (0..len / 8).map(|_| next_random(random) as u8).collect();
+        let mut encoded = vec![0; encoder.encode_len().div_ceil(8)];
+        let num_encoded_bits = encoder.encode(&msg, &mut encoded).unwrap();
+
+        let channel = (0..num_encoded_bits)
+            .map(|i| {
+                let bit = (encoded[i / 8] >> (7 - i % 8)) & 1;
+                let symbol = if bit == 1 { 1.0 } else { -1.0 };
+                2.0 * (symbol + sigma * next_gaussian(random)) / (sigma * sigma)
+            })
+            .collect();
+        (msg, channel)
+    }
+
+    fn bit_errors(msg: &[u8], llrs: &[f32]) -> usize {
+        llrs.iter()
+            .enumerate()
+            .filter(|&(i, llr)| (*llr > 0.0) != ((msg[i / 8] >> (7 - i % 8)) & 1 == 1))
+            .count()
+    }
+
+    #[test]
+    fn lte_permutation() {
+        // 3GPP TS 36.212 table 5.1.3-3, K = 40
+        let interleaver = QppInterleaver::new(40, 3, 10).unwrap();
+        assert_eq!(interleaver.num_bits(), 40);
+        assert_eq!(&interleaver.permutation()[..6], &[0, 13, 6, 19, 12, 25]);
+
+        let interleaver = QppInterleaver::new(6144, 263, 480).unwrap();
+        let mut seen = vec![false; 6144];
+        for index in interleaver.permutation() {
+            assert!(!seen[*index]);
+            seen[*index] = true;
+        }
+
+        assert_eq!(
+            QppInterleaver::new(40, 2, 10).err(),
+            Some(FecError::InvalidInterleaver)
+        );
+        assert_eq!(
+            QppInterleaver::new(44, 3, 10).err(),
+            Some(FecError::InvalidLength)
+        );
+    }
+
+    #[test]
+    fn noisy_round_trip() {
+        let mut random = 0x510e_527f;
+        for algorithm in &[MapAlgorithm::MaxLogMap, MapAlgorithm::LogMap] {
+            let (msg, channel) = noisy_frame(1024, 31, 64, 0.9, &mut random);
+            let interleaver = QppInterleaver::new(1024, 31, 64).unwrap();
+            let mut decoder = TurboDecoder::lte(interleaver, *algorithm, 8).unwrap();
+            let mut llrs = vec![0.0; 1024];
+            decoder.decode_llr(&channel, &mut llrs).unwrap();
+            assert_eq!(bit_errors(&msg, &llrs), 0, "{:?}", algorithm);
+
+            // the decisions of decode_soft follow the signs of the LLRs
+            let mut decoded = vec![0; 128];
+            let soft: Vec<u8> = channel
+                .iter()
+                .map(|llr| (127.5 + 16.0 * llr).clamp(0.0, 255.0) as u8)
+                .collect();
+            decoder.decode_soft(&soft, &mut decoded).unwrap();
+            assert_eq!(decoded, msg, "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn more_iterations_do_not_add_errors() {
+        let mut random = 0x9b05_688c;
+        let mut errors = vec![0; 4];
+        for _ in 0..4 {
+            let (msg, channel) = noisy_frame(1024, 31, 64, 1.1, &mut random);
+            for (max_iterations, errors) in [1, 2, 4, 8].iter().zip(errors.iter_mut()) {
+                let interleaver = QppInterleaver::new(1024, 31, 64).unwrap();
+                let mut decoder =
+                    TurboDecoder::lte(interleaver, MapAlgorithm::MaxLogMap, *max_iterations)
+                        .unwrap();
+                let mut llrs = vec![0.0; 1024];
+                let iterations = decoder.decode_llr(&channel, &mut llrs).unwrap();
+                assert!(iterations <= *max_iterations);
+                *errors += bit_errors(&msg, &llrs);
+            }
+        }
+        assert!(
+            errors[0] > 0,
+            "the channel should be noisy enough for errors"
+        );
+        for pair in errors.windows(2) {
+            assert!(pair[1] <= pair[0], "errors by iterations {:?}", errors);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_buffers() {
+        let mut encoder = TurboEncoder::lte(QppInterleaver::new(40, 3, 10).unwrap()).unwrap();
+        let mut dst = vec![0; encoder.encode_len().div_ceil(8)];
+        assert_eq!(
+            encoder.encode(&[0; 4], &mut dst),
+            Err(FecError::InvalidLength)
+        );
+        assert_eq!(
+            encoder.encode(&[0; 5], &mut dst[1..]),
+            Err(FecError::BufferTooSmall)
+        );
+        assert_eq!(encoder.encode(&[0; 5], &mut dst), Ok(132));
+
+        let mut rsc = RscEncoder::new(4, 0o15, &[0o13]).unwrap();
+        assert_eq!(
+            rsc.encode(&[0; 2], &mut [0; 4]),
+            Err(FecError::BufferTooSmall)
+        );
+        assert_eq!(rsc.encode(&[0; 2], &mut [0; 5]), Ok(38));
+    }
+}
@@ -1,3 +1,16 @@
+use FecError;
+
+use std::mem;
+
+/// Smallest supported rate (number of polynomials)
+pub const MIN_RATE: u32 = 2;
+/// Largest supported rate, as each symbol is packed into a byte
+pub const MAX_RATE: u32 = 8;
+/// Smallest supported order (constraint length)
+pub const MIN_ORDER: u32 = 4;
+/// Largest supported order, as polynomials are 16 bits wide
+pub const MAX_ORDER: u32 = 16;
+
 pub fn num_states_for_order(order: u32) -> u32 {
     1 << order
 }
@@ -25,3 +38,76 @@ pub fn conv_poly_table(rate: u32, order: u32, polys: &[u16]) -> Vec<u16> {
     }
     table
 }
+
+#[inline]
+pub fn soft_metric_distance(x: u32, soft: &[u8]) -> u32 {
+    let mut distance: u32 = 0;
+    for (i, symbol) in soft.iter().enumerate() {
+        let expected: i32 = if (x >> i) & 1 == 1 { 255 } else { 0 };
+        distance += (*symbol as i32 - expected).unsigned_abs();
+    }
+    distance
+}
+
+fn gf2_degree(p: u32) -> u32 {
+    31 - p.leading_zeros()
+}
+
+fn gf2_gcd(mut a: u32, mut b: u32) -> u32 {
+    while b != 0 {
+        while a != 0 && gf2_degree(a) >= gf2_degree(b) {
+            a ^= b << (gf2_degree(a) - gf2_degree(b));
+        }
+        mem::swap(&mut a, &mut b);
+    }
+    a
+}
+
+/// Check that the code is one we can encode and decode. A code is catastrophic,
+/// turning a finite number of channel errors into an unbounded number of decoded
+/// errors, when its polynomials share a common factor other than a power of x.
+pub fn check_code(rate: u32, order: u32, polys: &[u16]) -> Result<(), FecError> {
+    if !(MIN_RATE..=MAX_RATE).contains(&rate) {
+        return Err(FecError::InvalidRate);
+    }
+    if !(MIN_ORDER..=MAX_ORDER).contains(&order) {
+        return Err(FecError::InvalidOrder);
+    }
+    if polys.len() < rate as usize {
+        return Err(FecError::InvalidPolynomial);
+    }
+
+    let polys = &polys[..rate as usize];
+    let mut gcd: u32 = 0;
+    for poly in polys {
+        if *poly == 0 || (*poly as u32) >> order != 0 {
+            return Err(FecError::InvalidPolynomial);
+        }
+        gcd = gf2_gcd(gcd, *poly as u32);
+    }
+    if gcd.count_ones() != 1 {
+        return Err(FecError::InvalidPolynomial);
+    }
+    Ok(())
+}
+
+/// Check a recursive systematic code with one parity output per polynomial.
+/// The feedback polynomial must tap the newest bit, which is the one it feeds.
+pub fn check_recursive_code(order: u32, feedback: u16, polys: &[u16]) -> Result<(), FecError> {
+    let rate = polys.len() as u32 + 1;
+    if !(MIN_RATE..=MAX_RATE).contains(&rate) {
+        return Err(FecError::InvalidRate);
+    }
+    if !(MIN_ORDER..=MAX_ORDER).contains(&order) {
+        return Err(FecError::InvalidOrder);
+    }
+    if feedback & 1 == 0 || (feedback as u32) >> order != 0 {
+        return Err(FecError::InvalidPolynomial);
+    }
+    for poly in polys {
+        if *poly == 0 || (*poly as u32) >> order != 0 {
+            return Err(FecError::InvalidPolynomial);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,379 @@
+//! Cyclic redundancy checks
+//!
+//! Algorithms follow the usual parametric model, with a width of 8, 16 or 32
+//! bits, an initial register value, a final XOR, and whether the bits of each
+//! byte are processed least significant first (`reflect`). The check value of
+//! every algorithm in the catalog is listed with it, as the CRC of the ASCII
+//! string `123456789`.
+
+use FecError;
+
+/// A CRC algorithm of `width` bits with generator polynomial `poly`, written
+/// without its leading term
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Algorithm {
+    /// Number of bits in the CRC, 8, 16 or 32
+    pub width: u32,
+    /// Generator polynomial, most significant term in bit `width - 1`
+    pub poly: u32,
+    /// Register value before any data, unreflected
+    pub init: u32,
+    /// Process bytes least significant bit first, and reflect the result
+    pub reflect: bool,
+    /// Value XORed into the register to give the CRC
+    pub xor_out: u32,
+}
+
+impl Algorithm {
+    /// CRC-8 with polynomial x^8 + x^2 + x + 1, check value 0xf4
+    pub const CRC_8: Algorithm = Algorithm {
+        width: 8,
+        poly: 0x07,
+        init: 0,
+        reflect: false,
+        xor_out: 0,
+    };
+
+    /// CRC-16/CCITT-FALSE, as used by CCSDS frames, check value 0x29b1
+    pub const CRC_16_CCITT: Algorithm = Algorithm {
+        width: 16,
+        poly: 0x1021,
+        init: 0xffff,
+        reflect: false,
+        xor_out: 0,
+    };
+
+    /// CRC-16/X.25, as used by HDLC and AX.25 frames, check value 0x906e
+    pub const CRC_16_X25: Algorithm = Algorithm {
+        width: 16,
+        poly: 0x1021,
+        init: 0xffff,
+        reflect: true,
+        xor_out: 0xffff,
+    };
+
+    /// CRC-32 of IEEE 802.3, zlib and PNG, check value 0xcbf43926
+    pub const CRC_32_IEEE: Algorithm = Algorithm {
+        width: 32,
+        poly: 0x04c1_1db7,
+        init: 0xffff_ffff,
+        reflect: true,
+        xor_out: 0xffff_ffff,
+    };
+
+    /// CRC-32C (Castagnoli), as used by iSCSI and SCTP, check value 0xe3069283
+    pub const CRC_32C: Algorithm = Algorithm {
+        width: 32,
+        poly: 0x1edc_6f41,
+        init: 0xffff_ffff,
+        reflect: true,
+        xor_out: 0xffff_ffff,
+    };
+
+    /// Number of bytes the CRC takes up when appended to a buffer
+    pub fn num_bytes(&self) -> usize {
+        self.width as usize / 8
+    }
+}
+
+fn reflect_bits(value: u32, width: u32) -> u32 {
+    value.reverse_bits() >> (32 - width)
+}
+
+/// Table-driven CRC computation for an `Algorithm`
+///
+/// The register is kept in the low bits for reflected algorithms and in the
+/// high bits otherwise, so that every width shares the same tables.
+#[derive(Debug, Clone)]
+pub struct Crc {
+    algorithm: Algorithm,
+    tables: Vec<[u32; 256]>,
+}
+
+impl Crc {
+    /// Create a CRC with a single 256 entry table, processing a byte at a time
+    pub fn new(algorithm: Algorithm) -> Result<Crc, FecError> {
+        Crc::with_tables(algorithm, 1)
+    }
+
+    /// Create a CRC with eight tables, processing eight bytes at a time, which
+    /// is several times faster on long buffers
+    pub fn slicing_by_8(algorithm: Algorithm) -> Result<Crc, FecError> {
+        Crc::with_tables(algorithm, 8)
+    }
+
+    fn with_tables(algorithm: Algorithm, num_tables: usize) -> Result<Crc, FecError> {
+        let width = algorithm.width;
+        if width != 8 && width != 16 && width != 32 {
+            return Err(FecError::InvalidPolynomial);
+        }
+        let mask = u32::MAX >> (32 - width);
+        if algorithm.poly & !mask != 0
+            || algorithm.init & !mask != 0
+            || algorithm.xor_out & !mask != 0
+        {
+            return Err(FecError::InvalidPolynomial);
+        }
+
+        let mut tables = vec![[0; 256]; num_tables];
+        for (byte, entry) in tables[0].iter_mut().enumerate() {
+            *entry = if algorithm.reflect {
+                let poly = reflect_bits(algorithm.poly, width);
+                (0..8).fold(byte as u32, |crc, _| {
+                    if crc & 1 == 1 {
+                        (crc >> 1) ^ poly
+                    } else {
+                        crc >> 1
+                    }
+                })
+            } else {
+                let poly = algorithm.poly << (32 - width);
+                (0..8).fold((byte as u32) << 24, |crc, _| {
+                    if crc & 0x8000_0000 != 0 {
+                        (crc << 1) ^ poly
+                    } else {
+                        crc << 1
+                    }
+                })
+            };
+        }
+        // each further table advances the CRC of a byte by one more zero byte
+        for i in 1..num_tables {
+            for byte in 0..256 {
+                let previous = tables[i - 1][byte];
+                tables[i][byte] = if algorithm.reflect {
+                    (previous >> 8) ^ tables[0][(previous & 0xff) as usize]
+                } else {
+                    (previous << 8) ^ tables[0][(previous >> 24) as usize]
+                };
+            }
+        }
+
+        Ok(Crc { algorithm, tables })
+    }
+
+    /// Algorithm this CRC computes
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    /// Start a CRC computation over data fed in pieces
+    pub fn digest(&self) -> Digest<'_> {
+        let algorithm = self.algorithm;
+        let register = if algorithm.reflect {
+            reflect_bits(algorithm.init, algorithm.width)
+        } else {
+            algorithm.init << (32 - algorithm.width)
+        };
+        Digest {
+            crc: self,
+            register,
+        }
+    }
+
+    /// CRC of `data`
+    pub fn checksum(&self, data: &[u8]) -> u32 {
+        let mut digest = self.digest();
+        digest.update(data);
+        digest.finalize()
+    }
+
+    fn update_bytes(&self, mut register: u32, data: &[u8]) -> u32 {
+        let table = &self.tables[0];
+        for byte in data {
+            register = if self.algorithm.reflect {
+                (register >> 8) ^ table[((register ^ *byte as u32) & 0xff) as usize]
+            } else {
+                (register << 8) ^ table[((register >> 24) ^ *byte as u32) as usize]
+            };
+        }
+        register
+    }
+
+    fn update_slices(&self, mut register: u32, data: &[u8]) -> u32 {
+        let t = &self.tables;
+        let mut chunks = data.chunks_exact(8);
+        for chunk in chunks.by_ref() {
+            register = if self.algorithm.reflect {
+                let low = register ^ u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+                let high = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+                t[7][(low & 0xff) as usize]
+                    ^ t[6][((low >> 8) & 0xff) as usize]
+                    ^ t[5][((low >> 16) & 0xff) as usize]
+                    ^ t[4][(low >> 24) as usize]
+                    ^ t[3][(high & 0xff) as usize]
+                    ^ t[2][((high >> 8) & 0xff) as usize]
+                    ^ t[1][((high >> 16) & 0xff) as usize]
+                    ^ t[0][(high >> 24) as usize]
+            } else {
+                let high = register ^ u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+                let low = u32::from_be_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+                t[7][(high >> 24) as usize]
+                    ^ t[6][((high >> 16) & 0xff) as usize]
+                    ^ t[5][((high >> 8) & 0xff) as usize]
+                    ^ t[4][(high & 0xff) as usize]
+                    ^ t[3][(low >> 24) as usize]
+                    ^ t[2][((low >> 16) & 0xff) as usize]
+                    ^ t[1][((low >> 8) & 0xff) as usize]
+                    ^ t[0][(low & 0xff) as usize]
+            };
+        }
+        self.update_bytes(register, chunks.remainder())
+    }
+
+    /// Write the CRC of the first `len` bytes of `buf` right after them, and
+    /// return the length of the checked frame. Reflected CRCs are written
+    /// least significant byte first and the others most significant byte first,
+    /// as they are usually sent.
+    pub fn append(&self, buf: &mut [u8], len: usize) -> Result<usize, FecError> {
+        let num_bytes = self.algorithm.num_bytes();
+        if len > buf.len() {
+            return Err(FecError::InvalidLength);
+        }
+        if buf.len() - len < num_bytes {
+            return Err(FecError::BufferTooSmall);
+        }
+
+        let crc = self.checksum(&buf[..len]);
+        for (i, byte) in buf[len..len + num_bytes].iter_mut().enumerate() {
+            let shift = if self.algorithm.reflect {
+                8 * i
+            } else {
+                8 * (num_bytes - 1 - i)
+            };
+            *byte = (crc >> shift) as u8;
+        }
+        Ok(len + num_bytes)
+    }
+
+    /// Check a frame written by `append`, and return the length of its payload
+    pub fn verify(&self, frame: &[u8]) -> Result<usize, FecError> {
+        let num_bytes = self.algorithm.num_bytes();
+        if frame.len() < num_bytes {
+            return Err(FecError::InvalidLength);
+        }
+
+        let len = frame.len() - num_bytes;
+        let crc = self.checksum(&frame[..len]);
+        let expected = frame[len..]
+            .iter()
+            .enumerate()
+            .fold(0, |expected, (i, byte)| {
+                let shift = if self.algorithm.reflect {
+                    8 * i
+                } else {
+                    8 * (num_bytes - 1 - i)
+                };
+                expected | (*byte as u32) << shift
+            });
+        if crc != expected {
+            return Err(FecError::ChecksumMismatch);
+        }
+        Ok(len)
+    }
+}
+
+/// CRC computation in progress, from `Crc::digest`
+#[derive(Debug, Clone)]
+pub struct Digest<'a> {
+    crc: &'a Crc,
+    register: u32,
+}
+
+impl<'a> Digest<'a> {
+    /// Feed more data into the CRC
+    pub fn update(&mut self, data: &[u8]) {
+        self.register = if self.crc.tables.len() == 8 {
+            self.crc.update_slices(self.register, data)
+        } else {
+            self.crc.update_bytes(self.register, data)
+        };
+    }
+
+    /// CRC of all the data fed so far
+    pub fn finalize(self) -> u32 {
+        let algorithm = self.crc.algorithm;
+        let crc = if algorithm.reflect {
+            self.register
+        } else {
+            self.register >> (32 - algorithm.width)
+        };
+        crc ^ algorithm.xor_out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Algorithm, Crc};
+    use FecError;
+
+    const ALGORITHMS: [(Algorithm, u32); 5] = [
+        (Algorithm::CRC_8, 0xf4),
+        (Algorithm::CRC_16_CCITT, 0x29b1),
+        (Algorithm::CRC_16_X25, 0x906e),
+        (Algorithm::CRC_32_IEEE, 0xcbf4_3926),
+        (Algorithm::CRC_32C, 0xe306_9283),
+    ];
+
+    fn data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 167 + 13) as u8).collect()
+    }
+
+    #[test]
+    fn check_values() {
+        for (algorithm, check) in ALGORITHMS.iter() {
+            assert_eq!(Crc::new(*algorithm).unwrap().checksum(b"123456789"), *check);
+            assert_eq!(
+                Crc::slicing_by_8(*algorithm)
+                    .unwrap()
+                    .checksum(b"123456789"),
+                *check
+            );
+        }
+    }
+
+    #[test]
+    fn slicing_matches_table() {
+        for (algorithm, _) in ALGORITHMS.iter() {
+            let table = Crc::new(*algorithm).unwrap();
+            let slicing = Crc::slicing_by_8(*algorithm).unwrap();
+            for len in 0..100 {
+                let data = data(len);
+                assert_eq!(slicing.checksum(&data), table.checksum(&data));
+            }
+        }
+    }
+
+    #[test]
+    fn split_updates() {
+        let data = data(100);
+        for (algorithm, _) in ALGORITHMS.iter() {
+            let crc = Crc::slicing_by_8(*algorithm).unwrap();
+            let checksum = crc.checksum(&data);
+            for split in [0, 1, 7, 8, 9, 50, 100].iter() {
+                let mut digest = crc.digest();
+                digest.update(&data[..*split]);
+                digest.update(&data[*split..]);
+                assert_eq!(digest.finalize(), checksum);
+            }
+        }
+    }
+
+    #[test]
+    fn append_and_verify() {
+        for (algorithm, _) in ALGORITHMS.iter() {
+            let crc = Crc::new(*algorithm).unwrap();
+            let mut frame = data(24);
+            let len = frame.len() - algorithm.num_bytes();
+            assert_eq!(crc.append(&mut frame, len), Ok(24));
+            assert_eq!(crc.verify(&frame), Ok(len));
+
+            frame[3] ^= 0x10;
+            assert_eq!(crc.verify(&frame), Err(FecError::ChecksumMismatch));
+            assert_eq!(
+                crc.append(&mut frame, len + 1),
+                Err(FecError::BufferTooSmall)
+            );
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use std::error::Error;
+use std::fmt;
+
+/// Errors returned by the encoders and decoders in this crate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FecError {
+    /// The number of encoded bits does not make up a valid frame for the code,
+    /// or is more than the input buffer holds
+    InvalidLength,
+    /// The output buffer is too small to hold the result
+    BufferTooSmall,
+    /// The rate (number of polynomials) is unsupported or does not match the code
+    InvalidRate,
+    /// The order (constraint length) is unsupported
+    InvalidOrder,
+    /// A polynomial is unsupported for the given order
+    InvalidPolynomial,
+    /// The interleaver parameters do not describe a permutation of its length
+    InvalidInterleaver,
+    /// The block has more errors than the code can correct
+    Uncorrectable,
+    /// The checksum of the frame does not match its contents
+    ChecksumMismatch,
+}
+
+impl fmt::Display for FecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match *self {
+            FecError::InvalidLength => "invalid encoded length",
+            FecError::BufferTooSmall => "output buffer too small",
+            FecError::InvalidRate => "invalid rate",
+            FecError::InvalidOrder => "invalid order",
+            FecError::InvalidPolynomial => "invalid polynomial",
+            FecError::InvalidInterleaver => "invalid interleaver",
+            FecError::Uncorrectable => "too many errors to correct",
+            FecError::ChecksumMismatch => "checksum mismatch",
+        };
+        f.write_str(description)
+    }
+}
+
+impl Error for FecError {}
@@ -0,0 +1,471 @@
+//! Hamming and extended Hamming (SECDED) block codes
+//!
+//! Codewords are systematic: the data bits come first, then the parity bits,
+//! then for extended codes the overall parity bit. Each data bit is checked
+//! by the parity bits set in its syndrome column, which are taken in order
+//! from the numbers that are not powers of two, so that a single error
+//! anywhere in the codeword gives a syndrome that points back to it.
+//!
+//! Messages are packed bits, read most significant bit first, and are padded
+//! with zero bits to a whole number of codewords.
+
+use convolutional::bit::{BitReader, BitWriter};
+use FecError;
+
+/// Largest number of parity bits, which keeps codewords within 128 bits
+const MAX_PARITY_BITS: u32 = 7;
+/// Largest number of bits flipped by Chase decoding, 65536 candidates per codeword
+const MAX_CHASE_BITS: usize = 16;
+
+/// A Hamming code with `parity_bits` parity bits protecting `data_bits` data
+/// bits, shortened if there are fewer than `2^parity_bits - parity_bits - 1`
+/// of them, and extended with an overall parity bit if `extended`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Code {
+    pub parity_bits: u32,
+    pub data_bits: u32,
+    pub extended: bool,
+}
+
+impl Code {
+    /// Hamming(7,4)
+    pub const HAMMING_7_4: Code = Code {
+        parity_bits: 3,
+        data_bits: 4,
+        extended: false,
+    };
+
+    /// Hamming(15,11)
+    pub const HAMMING_15_11: Code = Code {
+        parity_bits: 4,
+        data_bits: 11,
+        extended: false,
+    };
+
+    /// Hamming(31,26)
+    pub const HAMMING_31_26: Code = Code {
+        parity_bits: 5,
+        data_bits: 26,
+        extended: false,
+    };
+
+    /// Extended Hamming(8,4), correcting one error and detecting two
+    pub const SECDED_8_4: Code = Code {
+        parity_bits: 3,
+        data_bits: 4,
+        extended: true,
+    };
+
+    /// Extended Hamming(127,120) shortened to (72,64), as used to protect
+    /// 64 bit memory words
+    pub const SECDED_72_64: Code = Code {
+        parity_bits: 7,
+        data_bits: 64,
+        extended: true,
+    };
+
+    /// Number of bits in a codeword
+    pub fn codeword_bits(&self) -> usize {
+        (self.data_bits + self.parity_bits) as usize + self.extended as usize
+    }
+}
+
+/// Syndrome column of every data bit, then of every parity bit
+fn check_code(code: Code) -> Result<Vec<u32>, FecError> {
+    if !(2..=MAX_PARITY_BITS).contains(&code.parity_bits) {
+        return Err(FecError::InvalidOrder);
+    }
+    let max_data_bits = (1 << code.parity_bits) - code.parity_bits - 1;
+    if code.data_bits == 0 || code.data_bits > max_data_bits {
+        return Err(FecError::InvalidRate);
+    }
+
+    let mut columns: Vec<u32> = (3..)
+        .filter(|column: &u32| !column.is_power_of_two())
+        .take(code.data_bits as usize)
+        .collect();
+    columns.extend((0..code.parity_bits).map(|bit| 1 << bit));
+    Ok(columns)
+}
+
+fn num_words(code: Code, len: usize) -> usize {
+    (8 * len).div_ceil(code.data_bits as usize)
+}
+
+/// Encoder for a Hamming `Code`, writing one codeword per `data_bits` bits of
+/// the message
+#[derive(Debug, Clone)]
+pub struct Encoder {
+    code: Code,
+    columns: Vec<u32>,
+}
+
+impl Encoder {
+    /// Create an encoder for `code`, which must have between 2 and 7 parity
+    /// bits and at most as many data bits as they can protect
+    pub fn new(code: Code) -> Result<Encoder, FecError> {
+        Ok(Encoder {
+            code,
+            columns: check_code(code)?,
+        })
+    }
+
+    /// Number of encoded bits for a message of `len` bytes
+    pub fn encode_len(&self, len: usize) -> usize {
+        num_words(self.code, len) * self.code.codeword_bits()
+    }
+
+    /// Encode `msg` and return the number of encoded bits
+    pub fn encode(&mut self, msg: &[u8], dst: &mut [u8]) -> Result<usize, FecError> {
+        let encode_len = self.encode_len(msg.len());
+        if 8 * dst.len() < encode_len {
+            return Err(FecError::BufferTooSmall);
+        }
+
+        let data_bits = self.code.data_bits as usize;
+        let mut remaining = 8 * msg.len();
+        let mut bit_reader = BitReader::new(msg);
+        let mut bit_writer = BitWriter::new(dst);
+        for _ in 0..num_words(self.code, msg.len()) {
+            let mut parity = 0;
+            let mut weight = 0;
+            for column in &self.columns[..data_bits] {
+                let bit = if remaining > 0 {
+                    remaining -= 1;
+                    bit_reader.read(1)
+                } else {
+                    0
+                };
+                if bit == 1 {
+                    parity ^= column;
+                    weight += 1;
+                }
+                bit_writer.write(bit, 1);
+            }
+            for bit in 0..self.code.parity_bits {
+                bit_writer.write((parity >> bit) as u8 & 1, 1);
+            }
+            if self.code.extended {
+                weight += parity.count_ones();
+                bit_writer.write((weight & 1) as u8, 1);
+            }
+        }
+
+        bit_writer.flush();
+        Ok(encode_len)
+    }
+}
+
+/// Syndrome decoder for the codewords of `Encoder`
+///
+/// Plain Hamming codes correct one error per codeword. Extended codes also
+/// detect two, as do shortened codes when the syndrome points outside the
+/// codeword, and fail with `FecError::Uncorrectable`.
+#[derive(Debug, Clone)]
+pub struct Decoder {
+    code: Code,
+    columns: Vec<u32>,
+    positions: Vec<Option<usize>>,
+    chase_bits: usize,
+    order: Vec<usize>,
+    reliability: Vec<u32>,
+}
+
+impl Decoder {
+    /// Create a decoder whose `decode_soft` tries every combination of flips
+    /// of the `chase_bits` least reliable bits of each codeword (Chase-II),
+    /// keeping the closest codeword to the soft symbols. With 0 it only
+    /// decodes the hard decisions; above 16 it is capped.
+    pub fn new(code: Code, chase_bits: usize) -> Result<Decoder, FecError> {
+        let columns = check_code(code)?;
+        let mut positions = vec![None; 1 << code.parity_bits];
+        for (position, column) in columns.iter().enumerate() {
+            positions[*column as usize] = Some(position);
+        }
+
+        Ok(Decoder {
+            code,
+            chase_bits: chase_bits.min(MAX_CHASE_BITS).min(code.codeword_bits()),
+            columns,
+            positions,
+            order: Vec::with_capacity(code.codeword_bits()),
+            reliability: Vec::with_capacity(code.codeword_bits()),
+        })
+    }
+
+    /// Number of bytes written by `decode` for a frame of `num_encoded_bits`
+    pub fn decode_len(&self, num_encoded_bits: usize) -> usize {
+        let num_words = num_encoded_bits / self.code.codeword_bits();
+        num_words * self.code.data_bits as usize / 8
+    }
+
+    /// Correct a codeword, with bit `i` holding codeword bit `i`
+    fn correct(&self, word: u128) -> Result<u128, FecError> {
+        let num_bits = self.columns.len();
+        let mut syndrome = 0;
+        for (i, column) in self.columns.iter().enumerate() {
+            if (word >> i) & 1 == 1 {
+                syndrome ^= column;
+            }
+        }
+
+        if self.code.extended {
+            let odd = word.count_ones() & 1 == 1;
+            match (syndrome, odd) {
+                (0, false) => Ok(word),
+                // only the overall parity bit is wrong
+                (0, true) => Ok(word ^ (1 << num_bits)),
+                (_, false) => Err(FecError::Uncorrectable),
+                (_, true) => match self.positions[syndrome as usize] {
+                    Some(position) => Ok(word ^ (1 << position)),
+                    None => Err(FecError::Uncorrectable),
+                },
+            }
+        } else if syndrome == 0 {
+            Ok(word)
+        } else {
+            match self.positions[syndrome as usize] {
+                Some(position) => Ok(word ^ (1 << position)),
+                None => Err(FecError::Uncorrectable),
+            }
+        }
+    }
+
+    fn check_frame(
+        &self,
+        num_encoded_bits: usize,
+        available_bits: usize,
+        msg: &[u8],
+    ) -> Result<(), FecError> {
+        if num_encoded_bits % self.code.codeword_bits() != 0 || num_encoded_bits > available_bits {
+            return Err(FecError::InvalidLength);
+        }
+        if msg.len() < self.decode_len(num_encoded_bits) {
+            return Err(FecError::BufferTooSmall);
+        }
+        Ok(())
+    }
+
+    fn write_data(&self, word: u128, bit_writer: &mut BitWriter) {
+        for i in 0..self.code.data_bits {
+            bit_writer.write((word >> i) as u8 & 1, 1);
+        }
+    }
+
+    /// Decode packed hard bits and return the number of decoded bytes.
+    /// Padding bits that fill whole bytes come back as trailing zero bytes.
+    pub fn decode(
+        &mut self,
+        encoded: &[u8],
+        num_encoded_bits: usize,
+        msg: &mut [u8],
+    ) -> Result<usize, FecError> {
+        self.check_frame(num_encoded_bits, 8 * encoded.len(), msg)?;
+        let decoded_len = self.decode_len(num_encoded_bits);
+
+        let codeword_bits = self.code.codeword_bits();
+        let mut bit_reader = BitReader::new(encoded);
+        let mut bit_writer = BitWriter::new(&mut msg[..decoded_len]);
+        for _ in 0..num_encoded_bits / codeword_bits {
+            let mut word = 0;
+            for i in 0..codeword_bits {
+                word |= (bit_reader.read(1) as u128) << i;
+            }
+            let word = self.correct(word)?;
+            self.write_data(word, &mut bit_writer);
+        }
+        Ok(decoded_len)
+    }
+
+    /// Correct a codeword of soft symbols, trying flips of its least reliable
+    /// bits and keeping the candidate closest to the symbols
+    fn correct_soft(&mut self, soft: &[u8]) -> Result<u128, FecError> {
+        let mut hard = 0;
+        self.reliability.clear();
+        for (i, symbol) in soft.iter().enumerate() {
+            if *symbol > 127 {
+                hard |= 1 << i;
+            }
+            self.reliability
+                .push((2 * *symbol as i32 - 255).unsigned_abs());
+        }
+
+        self.order.clear();
+        self.order.extend(0..soft.len());
+        let reliability = &self.reliability;
+        self.order.sort_by_key(|i| reliability[*i]);
+
+        let mut best: Option<(u32, u128)> = None;
+        for flips in 0..1usize << self.chase_bits {
+            let mut test = hard;
+            for (bit, position) in self.order[..self.chase_bits].iter().enumerate() {
+                if (flips >> bit) & 1 == 1 {
+                    test ^= 1 << position;
+                }
+            }
+            let candidate = match self.correct(test) {
+                Ok(candidate) => candidate,
+                Err(_) => continue,
+            };
+
+            // the soft distance grows with the reliability of every bit changed
+            let mut distance = 0;
+            let mut changed = candidate ^ hard;
+            while changed != 0 {
+                distance += self.reliability[changed.trailing_zeros() as usize];
+                changed &= changed - 1;
+            }
+            let closer = match best {
+                Some((best_distance, _)) => distance < best_distance,
+                None => true,
+            };
+            if closer {
+                best = Some((distance, candidate));
+            }
+        }
+        best.map(|(_, word)| word).ok_or(FecError::Uncorrectable)
+    }
+
+    /// Decode soft symbols, one byte per encoded bit, where 0 is a strong zero
+    /// and 255 is a strong one. Returns the number of decoded bytes.
+    pub fn decode_soft(
+        &mut self,
+        soft: &[u8],
+        num_encoded_bits: usize,
+        msg: &mut [u8],
+    ) -> Result<usize, FecError> {
+        self.check_frame(num_encoded_bits, soft.len(), msg)?;
+        let decoded_len = self.decode_len(num_encoded_bits);
+
+        let codeword_bits = self.code.codeword_bits();
+        let mut bit_writer = BitWriter::new(&mut msg[..decoded_len]);
+        for symbols in soft[..num_encoded_bits].chunks(codeword_bits) {
+            let word = self.correct_soft(symbols)?;
+            self.write_data(word, &mut bit_writer);
+        }
+        Ok(decoded_len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Code, Decoder, Encoder};
+    use FecError;
+
+    const CODES: [Code; 5] = [
+        Code::HAMMING_7_4,
+        Code::HAMMING_15_11,
+        Code::HAMMING_31_26,
+        Code::SECDED_8_4,
+        Code::SECDED_72_64,
+    ];
+
+    fn msg() -> Vec<u8> {
+        (0..40).map(|i| (i * 89 + 7) as u8).collect()
+    }
+
+    fn encode(code: Code, msg: &[u8]) -> (Vec<u8>, usize) {
+        let mut encoder = Encoder::new(code).unwrap();
+        let mut encoded = vec![0; encoder.encode_len(msg.len()).div_ceil(8)];
+        let num_encoded_bits = encoder.encode(msg, &mut encoded).unwrap();
+        (encoded, num_encoded_bits)
+    }
+
+    fn flip(encoded: &mut [u8], bit: usize) {
+        encoded[bit / 8] ^= 0x80 >> (bit % 8);
+    }
+
+    fn soft(encoded: &[u8], num_encoded_bits: usize) -> Vec<u8> {
+        (0..num_encoded_bits)
+            .map(|i| {
+                if (encoded[i / 8] >> (7 - i % 8)) & 1 == 1 {
+                    200
+                } else {
+                    55
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let msg = msg();
+        for code in CODES.iter() {
+            let (encoded, num_encoded_bits) = encode(*code, &msg);
+            let mut decoder = Decoder::new(*code, 0).unwrap();
+            let mut decoded = vec![0; decoder.decode_len(num_encoded_bits)];
+            decoder
+                .decode(&encoded, num_encoded_bits, &mut decoded)
+                .unwrap();
+            assert_eq!(&decoded[..msg.len()], &msg[..]);
+            assert!(decoded[msg.len()..].iter().all(|byte| *byte == 0));
+        }
+    }
+
+    #[test]
+    fn corrects_one_error_per_codeword() {
+        let msg = msg();
+        for code in CODES.iter() {
+            let (mut encoded, num_encoded_bits) = encode(*code, &msg);
+            let codeword_bits = code.codeword_bits();
+            for word in 0..num_encoded_bits / codeword_bits {
+                flip(&mut encoded, word * codeword_bits + word % codeword_bits);
+            }
+
+            let mut decoder = Decoder::new(*code, 0).unwrap();
+            let mut decoded = vec![0; decoder.decode_len(num_encoded_bits)];
+            decoder
+                .decode(&encoded, num_encoded_bits, &mut decoded)
+                .unwrap();
+            assert_eq!(&decoded[..msg.len()], &msg[..]);
+        }
+    }
+
+    #[test]
+    fn secded_detects_two_errors() {
+        let msg = msg();
+        for code in [Code::SECDED_8_4, Code::SECDED_72_64].iter() {
+            let codeword_bits = code.codeword_bits();
+            for second in 1..codeword_bits {
+                let (mut encoded, num_encoded_bits) = encode(*code, &msg);
+                flip(&mut encoded, 0);
+                flip(&mut encoded, second);
+
+                let mut decoder = Decoder::new(*code, 0).unwrap();
+                let mut decoded = vec![0; decoder.decode_len(num_encoded_bits)];
+                assert_eq!(
+                    decoder.decode(&encoded, num_encoded_bits, &mut decoded),
+                    Err(FecError::Uncorrectable)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn chase_corrects_unreliable_errors() {
+        let msg = msg();
+        let code = Code::HAMMING_15_11;
+        let (encoded, num_encoded_bits) = encode(code, &msg);
+        let mut soft = soft(&encoded, num_encoded_bits);
+
+        // two weak errors in every codeword are beyond hard decoding
+        for word in soft.chunks_mut(code.codeword_bits()) {
+            for symbol in word[2..4].iter_mut() {
+                *symbol = if *symbol > 127 { 120 } else { 135 };
+            }
+        }
+
+        let mut hard = Decoder::new(code, 0).unwrap();
+        let mut decoded = vec![0; hard.decode_len(num_encoded_bits)];
+        match hard.decode_soft(&soft, num_encoded_bits, &mut decoded) {
+            Ok(_) => assert_ne!(&decoded[..msg.len()], &msg[..]),
+            Err(err) => assert_eq!(err, FecError::Uncorrectable),
+        }
+
+        let mut chase = Decoder::new(code, 4).unwrap();
+        chase
+            .decode_soft(&soft, num_encoded_bits, &mut decoded)
+            .unwrap();
+        assert_eq!(&decoded[..msg.len()], &msg[..]);
+    }
+}
@@ -0,0 +1,130 @@
+use super::write_bits;
+use convolutional::bit::BitReader;
+use FecError;
+
+/// Interleaver that permutes blocks of a fixed number of symbols
+///
+/// Output symbol `i` of a block is input symbol `permutation()[i]`, and
+/// deinterleaving applies the inverse permutation. Symbols are either slice
+/// elements of any type, such as soft symbols or LLRs, or packed bits.
+#[derive(Debug, Clone)]
+pub struct Interleaver {
+    permutation: Vec<usize>,
+    inverse: Vec<usize>,
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+fn permute<T: Copy>(permutation: &[usize], src: &[T], dst: &mut [T]) -> Result<usize, FecError> {
+    if src.len() != permutation.len() {
+        return Err(FecError::InvalidLength);
+    }
+    if dst.len() < permutation.len() {
+        return Err(FecError::BufferTooSmall);
+    }
+
+    for (symbol, index) in dst.iter_mut().zip(permutation) {
+        *symbol = src[*index];
+    }
+    Ok(permutation.len())
+}
+
+fn permute_bits(permutation: &[usize], src: &[u8], dst: &mut [u8]) -> Result<usize, FecError> {
+    let num_bits = permutation.len();
+    if 8 * src.len() < num_bits {
+        return Err(FecError::InvalidLength);
+    }
+    if 8 * dst.len() < num_bits {
+        return Err(FecError::BufferTooSmall);
+    }
+
+    let mut bit_reader = BitReader::new(src);
+    let bits: Vec<u8> = (0..num_bits).map(|_| bit_reader.read(1)).collect();
+    write_bits(permutation.iter().map(|index| bits[*index]), num_bits, dst);
+    Ok(num_bits)
+}
+
+impl Interleaver {
+    /// Create an interleaver from the input symbol index of every output
+    /// symbol, which must be a permutation of a nonzero length
+    pub fn from_permutation(permutation: Vec<usize>) -> Result<Interleaver, FecError> {
+        if permutation.is_empty() {
+            return Err(FecError::InvalidLength);
+        }
+
+        let mut inverse = vec![usize::MAX; permutation.len()];
+        for (i, index) in permutation.iter().enumerate() {
+            if *index >= permutation.len() || inverse[*index] != usize::MAX {
+                return Err(FecError::InvalidInterleaver);
+            }
+            inverse[*index] = i;
+        }
+        Ok(Interleaver {
+            permutation,
+            inverse,
+        })
+    }
+
+    /// Create a block interleaver that writes `rows * cols` symbols into a
+    /// matrix row by row and reads them out column by column, so that symbols
+    /// adjacent in the output are `cols` apart in the input
+    pub fn block(rows: usize, cols: usize) -> Result<Interleaver, FecError> {
+        let mut permutation = Vec::with_capacity(rows * cols);
+        for col in 0..cols {
+            for row in 0..rows {
+                permutation.push(row * cols + col);
+            }
+        }
+        Interleaver::from_permutation(permutation)
+    }
+
+    /// Create a pseudo-random interleaver of `len` symbols. The permutation
+    /// only depends on `seed`, so that both ends of a link can build it.
+    pub fn random(len: usize, seed: u64) -> Result<Interleaver, FecError> {
+        let mut permutation: Vec<usize> = (0..len).collect();
+        let mut state = seed;
+        for i in (1..len).rev() {
+            let j = (splitmix64(&mut state) % (i as u64 + 1)) as usize;
+            permutation.swap(i, j);
+        }
+        Interleaver::from_permutation(permutation)
+    }
+
+    /// Number of symbols in a block
+    pub fn num_symbols(&self) -> usize {
+        self.permutation.len()
+    }
+
+    /// Input symbol index for every output symbol
+    pub fn permutation(&self) -> &[usize] {
+        &self.permutation
+    }
+
+    /// Interleave a block of `num_symbols` symbols, returning the number of
+    /// symbols written to `dst`
+    pub fn interleave<T: Copy>(&self, src: &[T], dst: &mut [T]) -> Result<usize, FecError> {
+        permute(&self.permutation, src, dst)
+    }
+
+    /// Undo `interleave`, returning the number of symbols written to `dst`
+    pub fn deinterleave<T: Copy>(&self, src: &[T], dst: &mut [T]) -> Result<usize, FecError> {
+        permute(&self.inverse, src, dst)
+    }
+
+    /// Interleave a block of `num_symbols` packed bits, returning the number
+    /// of bits written to `dst`. Bits of `dst` past them are left as they were.
+    pub fn interleave_bits(&self, src: &[u8], dst: &mut [u8]) -> Result<usize, FecError> {
+        permute_bits(&self.permutation, src, dst)
+    }
+
+    /// Undo `interleave_bits`, returning the number of bits written to `dst`
+    pub fn deinterleave_bits(&self, src: &[u8], dst: &mut [u8]) -> Result<usize, FecError> {
+        permute_bits(&self.inverse, src, dst)
+    }
+}
@@ -0,0 +1,138 @@
+use super::write_bits;
+use convolutional::bit::BitReader;
+use FecError;
+
+use std::mem;
+
+/// Convolutional (Forney) interleaver, or its deinterleaver
+///
+/// Symbols go to `branches` delay lines in turn. Branch `b` of the interleaver
+/// holds `b * delay` symbols and that of the deinterleaver
+/// `(branches - 1 - b) * delay`, so that every symbol comes out of the pair
+/// `latency` symbols after it went in. Until then, the output comes from the
+/// initially zeroed delay lines.
+///
+/// Symbols are either slice elements of any type, such as soft symbols or
+/// LLRs, or, for an interleaver of bytes, packed bits; the interleaver keeps
+/// its state across calls in both cases. The delay lines start out filled with
+/// `T::default()`.
+#[derive(Debug, Clone)]
+pub struct ConvolutionalInterleaver<T = u8> {
+    branches: usize,
+    delay: usize,
+    deinterleaver: bool,
+    lines: Vec<Vec<T>>,
+    heads: Vec<usize>,
+    branch: usize,
+}
+
+impl<T: Copy + Default> ConvolutionalInterleaver<T> {
+    /// Create an interleaver with `branches` delay lines, growing by `delay`
+    /// symbols from one branch to the next
+    pub fn new(branches: usize, delay: usize) -> Result<ConvolutionalInterleaver<T>, FecError> {
+        if branches == 0 {
+            return Err(FecError::InvalidLength);
+        }
+        Ok(ConvolutionalInterleaver::with_lines(branches, delay, false))
+    }
+
+    fn with_lines(
+        branches: usize,
+        delay: usize,
+        deinterleaver: bool,
+    ) -> ConvolutionalInterleaver<T> {
+        let lines = (0..branches)
+            .map(|branch| {
+                let len = if deinterleaver {
+                    (branches - 1 - branch) * delay
+                } else {
+                    branch * delay
+                };
+                vec![T::default(); len]
+            })
+            .collect();
+        ConvolutionalInterleaver {
+            branches,
+            delay,
+            deinterleaver,
+            lines,
+            heads: vec![0; branches],
+            branch: 0,
+        }
+    }
+
+    /// Create the deinterleaver that undoes this interleaver, or the other way
+    /// around, starting from empty delay lines
+    pub fn inverse(&self) -> ConvolutionalInterleaver<T> {
+        ConvolutionalInterleaver::with_lines(self.branches, self.delay, !self.deinterleaver)
+    }
+
+    /// Number of symbols by which a symbol is delayed through the interleaver
+    /// and the deinterleaver together
+    pub fn latency(&self) -> usize {
+        self.branches * (self.branches - 1) * self.delay
+    }
+
+    /// Empty the delay lines and start over at the first branch
+    pub fn reset(&mut self) {
+        for line in self.lines.iter_mut() {
+            for symbol in line.iter_mut() {
+                *symbol = T::default();
+            }
+        }
+        for head in self.heads.iter_mut() {
+            *head = 0;
+        }
+        self.branch = 0;
+    }
+
+    fn next(&mut self, symbol: T) -> T {
+        let line = &mut self.lines[self.branch];
+        let head = &mut self.heads[self.branch];
+        self.branch = (self.branch + 1) % self.branches;
+
+        if line.is_empty() {
+            return symbol;
+        }
+        let oldest = mem::replace(&mut line[*head], symbol);
+        *head = (*head + 1) % line.len();
+        oldest
+    }
+
+    /// Push symbols, writing as many symbols to `dst`. Returns the number of
+    /// symbols written.
+    pub fn push(&mut self, src: &[T], dst: &mut [T]) -> Result<usize, FecError> {
+        if dst.len() < src.len() {
+            return Err(FecError::BufferTooSmall);
+        }
+
+        for (out, symbol) in dst.iter_mut().zip(src) {
+            *out = self.next(*symbol);
+        }
+        Ok(src.len())
+    }
+}
+
+impl ConvolutionalInterleaver<u8> {
+    /// Push `num_bits` packed bits, writing as many bits to `dst`, starting at
+    /// its first byte and leaving the bits past them as they were. Returns the
+    /// number of bits written.
+    pub fn push_bits(
+        &mut self,
+        src: &[u8],
+        num_bits: usize,
+        dst: &mut [u8],
+    ) -> Result<usize, FecError> {
+        if 8 * src.len() < num_bits {
+            return Err(FecError::InvalidLength);
+        }
+        if 8 * dst.len() < num_bits {
+            return Err(FecError::BufferTooSmall);
+        }
+
+        let mut bit_reader = BitReader::new(src);
+        let bits = (0..num_bits).map(|_| self.next(bit_reader.read(1)));
+        write_bits(bits, num_bits, dst);
+        Ok(num_bits)
+    }
+}
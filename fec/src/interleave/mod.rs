@@ -0,0 +1,79 @@
+//! Interleavers that spread burst errors across a frame
+//!
+//! The Viterbi decoder assumes that channel errors are independent, and a
+//! burst of them, e.g. from an audio dropout, easily overwhelms it. Interleaving
+//! the encoded symbols before sending them, and deinterleaving them before
+//! decoding, turns such a burst into errors scattered far apart in the frame.
+//!
+//! `Interleaver` permutes fixed-size blocks, either row/column or
+//! pseudo-random, while `ConvolutionalInterleaver` works on a continuous stream
+//! of symbols at a fraction of the latency.
+
+mod block;
+mod forney;
+
+pub use self::block::Interleaver;
+pub use self::forney::ConvolutionalInterleaver;
+use convolutional::bit::BitWriter;
+
+/// Write `num_bits` bits to the start of `dst`, leaving the bits past them in
+/// its last byte as they were
+fn write_bits<I: Iterator<Item = u8>>(bits: I, num_bits: usize, dst: &mut [u8]) {
+    let trailing = dst.get(num_bits / 8).cloned().unwrap_or(0);
+    {
+        let mut bit_writer = BitWriter::new(dst);
+        for bit in bits.take(num_bits) {
+            bit_writer.write(bit, 1);
+        }
+        bit_writer.flush();
+    }
+
+    let last_len = num_bits % 8;
+    if last_len != 0 {
+        let mask = 0xff >> last_len;
+        let last = &mut dst[num_bits / 8];
+        *last = (*last & !mask) | (trailing & mask);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConvolutionalInterleaver, Interleaver};
+
+    #[test]
+    fn bits_past_the_block_are_kept() {
+        let interleaver = Interleaver::block(3, 4).unwrap();
+        let src = [0b1011_0110, 0b1101_0000];
+        let mut interleaved = [0xff; 2];
+        assert_eq!(interleaver.interleave_bits(&src, &mut interleaved), Ok(12));
+        assert_eq!(interleaved[1] & 0x0f, 0x0f);
+
+        let mut deinterleaved = [0x05; 2];
+        assert_eq!(
+            interleaver.deinterleave_bits(&interleaved, &mut deinterleaved),
+            Ok(12)
+        );
+        assert_eq!(deinterleaved, [0b1011_0110, 0b1101_0101]);
+
+        let mut convolutional = ConvolutionalInterleaver::new(3, 1).unwrap();
+        let mut dst = [0xff];
+        assert_eq!(convolutional.push_bits(&[0xff], 5, &mut dst), Ok(5));
+        assert_eq!(dst[0] & 0x07, 0x07);
+    }
+
+    #[test]
+    fn convolutional_round_trip_of_llrs() {
+        let mut interleaver = ConvolutionalInterleaver::new(4, 2).unwrap();
+        let mut deinterleaver = interleaver.inverse();
+        let latency = interleaver.latency();
+
+        let src: Vec<f32> = (0..100).map(|i| i as f32 - 50.0).collect();
+        let mut interleaved = vec![0.0; src.len()];
+        let mut dst = vec![0.0; src.len()];
+        interleaver.push(&src, &mut interleaved).unwrap();
+        deinterleaver.push(&interleaved, &mut dst).unwrap();
+
+        assert!(dst[..latency].iter().all(|llr| *llr == 0.0));
+        assert_eq!(&dst[latency..], &src[..src.len() - latency]);
+    }
+}
@@ -1,4 +1,15 @@
+pub mod concatenated;
 pub mod convolutional;
+pub mod crc;
+mod error;
+pub mod hamming;
+pub mod interleave;
+pub mod reed_solomon;
+pub mod scramble;
+
+pub use error::FecError;
 
 #[macro_use]
 extern crate lazy_static;
+#[cfg(feature = "parallel")]
+extern crate rayon;
@@ -0,0 +1,21 @@
+//! Catalog of standard Reed-Solomon codes
+
+/// A Reed-Solomon code over GF(2^8), with the parameters of `Encoder::new`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Code {
+    pub primitive_polynomial: u16,
+    pub first_consecutive_root: usize,
+    pub root_gap: usize,
+    pub num_roots: usize,
+}
+
+impl Code {
+    /// CCSDS (255,223) code. CCSDS sends its symbols in a dual basis, which
+    /// this crate does not convert to, so blocks are in the conventional basis.
+    pub const CCSDS: Code = Code {
+        primitive_polynomial: 0x187,
+        first_consecutive_root: 112,
+        root_gap: 11,
+        num_roots: 32,
+    };
+}
@@ -0,0 +1,434 @@
+use super::codes::Code;
+use super::field::Field;
+use super::util;
+use FecError;
+
+use std::mem;
+
+/// Reed-Solomon decoder for the blocks of `Encoder`
+///
+/// Decoding computes the syndromes of the block, finds the error locator
+/// polynomial with Berlekamp-Massey, the error positions as its roots with a
+/// Chien search, and the error values with Forney's formula. Known erasures
+/// seed the locator, so that they only cost one parity byte each instead of two.
+#[derive(Debug, Clone)]
+pub struct Decoder {
+    field: Field,
+    first_consecutive_root: usize,
+    root_gap: usize,
+    num_roots: usize,
+    roots: Vec<u8>,
+    block: Vec<u8>,
+    syndromes: Vec<u8>,
+    // polynomials below are stored lowest degree first
+    locator: Vec<u8>,
+    previous_locator: Vec<u8>,
+    scratch: Vec<u8>,
+    evaluator: Vec<u8>,
+    positions: Vec<usize>,
+}
+
+impl Decoder {
+    /// Create a decoder, with the same parameters as `Encoder::new`
+    pub fn new(
+        primitive_polynomial: u16,
+        first_consecutive_root: usize,
+        root_gap: usize,
+        num_roots: usize,
+    ) -> Result<Decoder, FecError> {
+        util::check_code(first_consecutive_root, root_gap, num_roots)?;
+
+        let field = Field::new(primitive_polynomial)?;
+        let roots = util::generator_roots(&field, first_consecutive_root, root_gap, num_roots);
+        Ok(Decoder {
+            field,
+            first_consecutive_root,
+            root_gap,
+            num_roots,
+            roots,
+            block: Vec::with_capacity(util::MAX_BLOCK_LEN),
+            syndromes: vec![0; num_roots],
+            locator: vec![0; num_roots + 1],
+            previous_locator: vec![0; num_roots + 1],
+            scratch: vec![0; num_roots + 1],
+            evaluator: vec![0; num_roots],
+            positions: Vec::with_capacity(num_roots),
+        })
+    }
+
+    /// Create a decoder for a code from the `codes` catalog
+    pub fn from_code(code: Code) -> Result<Decoder, FecError> {
+        Decoder::new(
+            code.primitive_polynomial,
+            code.first_consecutive_root,
+            code.root_gap,
+            code.num_roots,
+        )
+    }
+
+    /// Number of message bytes in a block of `len` bytes
+    pub fn decode_len(&self, len: usize) -> usize {
+        len.saturating_sub(self.num_roots)
+    }
+
+    /// Locator of the byte `power` places from the end of the block
+    fn locator_of(&self, power: usize) -> u8 {
+        self.field.exp(self.root_gap * power)
+    }
+
+    /// Returns whether the block has any errors
+    fn compute_syndromes(&mut self) -> bool {
+        let mut any_errors = false;
+        for (syndrome, root) in self.syndromes.iter_mut().zip(&self.roots) {
+            *syndrome = self.field.eval(&self.block, *root);
+            any_errors |= *syndrome != 0;
+        }
+        any_errors
+    }
+
+    /// Set the locator to the product of `1 - X x` over the locators `X` of
+    /// the erased bytes of a block of `block_len` bytes
+    fn erasure_locator(&mut self, erasures: &[usize], block_len: usize) {
+        for coeff in self.locator.iter_mut() {
+            *coeff = 0;
+        }
+        self.locator[0] = 1;
+        for (degree, index) in erasures.iter().enumerate() {
+            let locator = self.locator_of(block_len - 1 - index);
+            for i in (1..=degree + 1).rev() {
+                self.locator[i] ^= self.field.mul(self.locator[i - 1], locator);
+            }
+        }
+    }
+
+    /// Find the shortest errata locator that generates the syndromes, starting
+    /// from the locator of `num_erasures` erasures, and return its degree
+    fn berlekamp_massey(&mut self, num_erasures: usize) -> usize {
+        self.previous_locator.copy_from_slice(&self.locator);
+
+        let mut degree = num_erasures;
+        let mut shift = 1;
+        let mut previous_discrepancy = 1;
+        for r in num_erasures..self.num_roots {
+            let mut discrepancy = self.syndromes[r];
+            for i in 1..=degree.min(r) {
+                discrepancy ^= self.field.mul(self.locator[i], self.syndromes[r - i]);
+            }
+
+            if discrepancy == 0 {
+                shift += 1;
+                continue;
+            }
+
+            // locator -= discrepancy / previous_discrepancy * x^shift * previous_locator
+            let scale = self.field.div(discrepancy, previous_discrepancy);
+            self.scratch.copy_from_slice(&self.locator);
+            for i in shift..=self.num_roots {
+                self.locator[i] ^= self.field.mul(scale, self.previous_locator[i - shift]);
+            }
+
+            if 2 * degree <= r + num_erasures {
+                degree = r + 1 + num_erasures - degree;
+                mem::swap(&mut self.previous_locator, &mut self.scratch);
+                previous_discrepancy = discrepancy;
+                shift = 1;
+            } else {
+                shift += 1;
+            }
+        }
+        degree
+    }
+
+    /// Find the positions, counted from the end of a block of `block_len`
+    /// bytes, whose inverse locators are roots of the error locator
+    fn chien_search(&mut self, block_len: usize) {
+        self.positions.clear();
+        for power in 0..block_len {
+            let inverse = self.field.inv(self.locator_of(power));
+            let mut value = 0;
+            for coeff in self.locator.iter().rev() {
+                value = self.field.mul(value, inverse) ^ coeff;
+            }
+            if value == 0 {
+                self.positions.push(power);
+            }
+        }
+    }
+
+    /// Compute the error value at each position and correct the block,
+    /// returning the number of bytes that changed
+    fn forney(&mut self) -> Result<usize, FecError> {
+        // evaluator = syndromes * locator mod x^num_roots
+        for i in 0..self.num_roots {
+            let mut coeff = 0;
+            for j in 0..=i {
+                coeff ^= self.field.mul(self.syndromes[j], self.locator[i - j]);
+            }
+            self.evaluator[i] = coeff;
+        }
+
+        let block_len = self.block.len();
+        let mut num_corrected = 0;
+        for power in self.positions.iter() {
+            let locator = self.locator_of(*power);
+            let inverse = self.field.inv(locator);
+
+            let mut evaluator = 0;
+            for coeff in self.evaluator.iter().rev() {
+                evaluator = self.field.mul(evaluator, inverse) ^ coeff;
+            }
+            // the formal derivative keeps only the odd powers
+            let inverse_squared = self.field.mul(inverse, inverse);
+            let mut derivative = 0;
+            for coeff in self.locator.iter().skip(1).step_by(2).rev() {
+                derivative = self.field.mul(derivative, inverse_squared) ^ coeff;
+            }
+            if derivative == 0 {
+                return Err(FecError::Uncorrectable);
+            }
+
+            // scale by locator^(1 - first_consecutive_root)
+            let exponent = (self.field.log(locator) * (256 - self.first_consecutive_root)) % 255;
+            let error = self.field.mul(
+                self.field.exp(exponent),
+                self.field.div(evaluator, derivative),
+            );
+            self.block[block_len - 1 - power] ^= error;
+            if error != 0 {
+                num_corrected += 1;
+            }
+        }
+        Ok(num_corrected)
+    }
+
+    /// Indices in the block of the errata located by the last decode, erased
+    /// bytes included even if they turned out to be right
+    pub fn errata_positions<'a>(&'a self) -> impl Iterator<Item = usize> + 'a {
+        let block_len = self.block.len();
+        self.positions
+            .iter()
+            .map(move |power| block_len - 1 - power)
+    }
+
+    /// Decode a block of up to 255 bytes, writing its message bytes to `msg`.
+    /// Corrects up to `num_roots / 2` errors, and returns the number of
+    /// corrected bytes.
+    pub fn decode(&mut self, encoded: &[u8], msg: &mut [u8]) -> Result<usize, FecError> {
+        self.decode_with_erasures(encoded, &[], msg)
+    }
+
+    /// Decode a block whose bytes at the indices in `erasures` are known to be
+    /// unreliable. Corrects `e` errors besides `f` erasures as long as
+    /// `2e + f <= num_roots`, and returns the number of corrected bytes.
+    pub fn decode_with_erasures(
+        &mut self,
+        encoded: &[u8],
+        erasures: &[usize],
+        msg: &mut [u8],
+    ) -> Result<usize, FecError> {
+        if encoded.len() <= self.num_roots || encoded.len() > util::MAX_BLOCK_LEN {
+            return Err(FecError::InvalidLength);
+        }
+        for (i, index) in erasures.iter().enumerate() {
+            if *index >= encoded.len() || erasures[..i].contains(index) {
+                return Err(FecError::InvalidLength);
+            }
+        }
+        if erasures.len() > self.num_roots {
+            return Err(FecError::Uncorrectable);
+        }
+        let msg_len = self.decode_len(encoded.len());
+        if msg.len() < msg_len {
+            return Err(FecError::BufferTooSmall);
+        }
+
+        self.block.clear();
+        self.block.extend_from_slice(encoded);
+        self.positions.clear();
+
+        let mut num_corrected = 0;
+        if self.compute_syndromes() {
+            self.erasure_locator(erasures, encoded.len());
+            let degree = self.berlekamp_massey(erasures.len());
+            let num_errors = degree.saturating_sub(erasures.len());
+            if 2 * num_errors + erasures.len() > self.num_roots {
+                return Err(FecError::Uncorrectable);
+            }
+
+            // every root must fall inside the block, or the errors are beyond repair
+            self.chien_search(encoded.len());
+            if self.positions.len() != degree {
+                return Err(FecError::Uncorrectable);
+            }
+
+            num_corrected = self.forney()?;
+        }
+
+        msg[..msg_len].copy_from_slice(&self.block[..msg_len]);
+        Ok(num_corrected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::codes::Code;
+    use super::super::encoder::Encoder;
+    use super::Decoder;
+    use FecError;
+
+    const CODES: [Code; 3] = [
+        Code {
+            primitive_polynomial: 0x11d,
+            first_consecutive_root: 0,
+            root_gap: 1,
+            num_roots: 16,
+        },
+        Code {
+            primitive_polynomial: 0x11d,
+            first_consecutive_root: 1,
+            root_gap: 1,
+            num_roots: 10,
+        },
+        Code::CCSDS,
+    ];
+
+    fn encode(code: Code, msg_len: usize) -> Vec<u8> {
+        let msg: Vec<u8> = (0..msg_len).map(|i| (i * 53 + 29) as u8).collect();
+        let mut encoder = Encoder::from_code(code).unwrap();
+        let mut block = vec![0; encoder.encode_len(msg_len)];
+        encoder.encode(&msg, &mut block).unwrap();
+        block
+    }
+
+    /// `num_errors` distinct indices spread over a block of `block_len` bytes
+    fn positions(block_len: usize, num_errors: usize, offset: usize) -> Vec<usize> {
+        (0..num_errors)
+            .map(|i| (offset + i * block_len / num_errors) % block_len)
+            .collect()
+    }
+
+    fn corrupt(block: &[u8], positions: &[usize]) -> Vec<u8> {
+        let mut corrupted = block.to_vec();
+        for (i, position) in positions.iter().enumerate() {
+            corrupted[*position] ^= (i as u8).wrapping_mul(37) | 1;
+        }
+        corrupted
+    }
+
+    #[test]
+    fn round_trip() {
+        for code in CODES.iter() {
+            let block = encode(*code, 255 - code.num_roots);
+            let mut decoder = Decoder::from_code(*code).unwrap();
+            let mut msg = vec![0; decoder.decode_len(block.len())];
+            assert_eq!(decoder.decode(&block, &mut msg), Ok(0));
+            assert_eq!(&msg[..], &block[..msg.len()]);
+        }
+    }
+
+    #[test]
+    fn corrects_half_as_many_errors_as_roots() {
+        for code in CODES.iter() {
+            let block = encode(*code, 255 - code.num_roots);
+            let mut decoder = Decoder::from_code(*code).unwrap();
+            let mut msg = vec![0; decoder.decode_len(block.len())];
+            for offset in 0..5 {
+                let errors = positions(block.len(), code.num_roots / 2, 3 * offset);
+                let corrupted = corrupt(&block, &errors);
+                assert_eq!(decoder.decode(&corrupted, &mut msg), Ok(code.num_roots / 2));
+                assert_eq!(&msg[..], &block[..msg.len()]);
+            }
+        }
+    }
+
+    #[test]
+    fn shortened_blocks() {
+        for code in CODES.iter() {
+            for msg_len in [1, 20, 100].iter() {
+                let block = encode(*code, *msg_len);
+                let errors = positions(block.len(), code.num_roots / 2, 1);
+                let corrupted = corrupt(&block, &errors);
+
+                let mut decoder = Decoder::from_code(*code).unwrap();
+                let mut msg = vec![0; *msg_len];
+                assert_eq!(decoder.decode(&corrupted, &mut msg), Ok(code.num_roots / 2));
+                assert_eq!(&msg[..], &block[..*msg_len]);
+            }
+        }
+    }
+
+    #[test]
+    fn reports_errors_beyond_capacity() {
+        let code = Code::CCSDS;
+        let block = encode(code, 223);
+        let errors = positions(block.len(), code.num_roots / 2 + 1, 0);
+        let corrupted = corrupt(&block, &errors);
+
+        let mut decoder = Decoder::from_code(code).unwrap();
+        let mut msg = vec![0; 223];
+        assert_eq!(
+            decoder.decode(&corrupted, &mut msg),
+            Err(FecError::Uncorrectable)
+        );
+    }
+
+    #[test]
+    fn corrects_as_many_erasures_as_roots() {
+        for code in CODES.iter() {
+            let block = encode(*code, 255 - code.num_roots);
+            let erasures = positions(block.len(), code.num_roots, 2);
+            let corrupted = corrupt(&block, &erasures);
+
+            let mut decoder = Decoder::from_code(*code).unwrap();
+            let mut msg = vec![0; decoder.decode_len(block.len())];
+            assert_eq!(
+                decoder.decode_with_erasures(&corrupted, &erasures, &mut msg),
+                Ok(code.num_roots)
+            );
+            assert_eq!(&msg[..], &block[..msg.len()]);
+
+            let mut errata: Vec<usize> = decoder.errata_positions().collect();
+            errata.sort();
+            let mut expected = erasures.clone();
+            expected.sort();
+            assert_eq!(errata, expected);
+        }
+    }
+
+    #[test]
+    fn corrects_erasures_and_errors() {
+        for code in CODES.iter() {
+            let block = encode(*code, 150);
+            let mut decoder = Decoder::from_code(*code).unwrap();
+            let mut msg = vec![0; 150];
+            for num_erasures in 0..=code.num_roots {
+                let num_errors = (code.num_roots - num_erasures) / 2;
+                let errata = positions(block.len(), num_erasures + num_errors, 4);
+                let corrupted = corrupt(&block, &errata);
+
+                let erasures = &errata[..num_erasures];
+                assert_eq!(
+                    decoder.decode_with_erasures(&corrupted, erasures, &mut msg),
+                    Ok(num_erasures + num_errors),
+                    "{} erasures and {} errors",
+                    num_erasures,
+                    num_errors
+                );
+                assert_eq!(&msg[..], &block[..150]);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_more_erasures_than_roots() {
+        let code = CODES[1];
+        let block = encode(code, 100);
+        let erasures = positions(block.len(), code.num_roots + 1, 0);
+        let mut decoder = Decoder::from_code(code).unwrap();
+        let mut msg = vec![0; 100];
+        assert_eq!(
+            decoder.decode_with_erasures(&block, &erasures, &mut msg),
+            Err(FecError::Uncorrectable)
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use super::codes::Code;
+use super::field::Field;
+use super::util;
+use FecError;
+
+/// Systematic Reed-Solomon encoder over GF(2^8)
+///
+/// A block is the message followed by `num_roots` parity bytes, with the first
+/// byte holding the highest degree coefficient. Messages shorter than
+/// `255 - num_roots` bytes give shortened blocks.
+#[derive(Debug, Clone)]
+pub struct Encoder {
+    field: Field,
+    num_roots: usize,
+    generator: Vec<u8>,
+    parity: Vec<u8>,
+}
+
+impl Encoder {
+    /// Create an encoder for the code whose generator polynomial has the
+    /// `num_roots` roots `2^(root_gap * (first_consecutive_root + i))` in the
+    /// field of `primitive_polynomial`. `root_gap` must be coprime with 255.
+    pub fn new(
+        primitive_polynomial: u16,
+        first_consecutive_root: usize,
+        root_gap: usize,
+        num_roots: usize,
+    ) -> Result<Encoder, FecError> {
+        util::check_code(first_consecutive_root, root_gap, num_roots)?;
+
+        let field = Field::new(primitive_polynomial)?;
+        let roots = util::generator_roots(&field, first_consecutive_root, root_gap, num_roots);
+        let generator = util::generator_polynomial(&field, &roots);
+        Ok(Encoder {
+            field,
+            num_roots,
+            generator,
+            parity: vec![0; num_roots],
+        })
+    }
+
+    /// Create an encoder for a code from the `codes` catalog
+    pub fn from_code(code: Code) -> Result<Encoder, FecError> {
+        Encoder::new(
+            code.primitive_polynomial,
+            code.first_consecutive_root,
+            code.root_gap,
+            code.num_roots,
+        )
+    }
+
+    /// Number of parity bytes added to each block
+    pub fn num_roots(&self) -> usize {
+        self.num_roots
+    }
+
+    /// Number of bytes in the block for a message of `len` bytes
+    pub fn encode_len(&self, len: usize) -> usize {
+        len + self.num_roots
+    }
+
+    /// Encode `msg` into `dst`, returning the number of bytes in the block
+    pub fn encode(&mut self, msg: &[u8], dst: &mut [u8]) -> Result<usize, FecError> {
+        let block_len = self.encode_len(msg.len());
+        if msg.is_empty() || block_len > util::MAX_BLOCK_LEN {
+            return Err(FecError::InvalidLength);
+        }
+        if dst.len() < block_len {
+            return Err(FecError::BufferTooSmall);
+        }
+
+        // divide msg * x^num_roots by the generator, keeping the remainder
+        for parity in self.parity.iter_mut() {
+            *parity = 0;
+        }
+        for byte in msg {
+            let feedback = byte ^ self.parity[0];
+            for i in 0..self.num_roots - 1 {
+                self.parity[i] =
+                    self.parity[i + 1] ^ self.field.mul(feedback, self.generator[i + 1]);
+            }
+            self.parity[self.num_roots - 1] =
+                self.field.mul(feedback, self.generator[self.num_roots]);
+        }
+
+        dst[..msg.len()].copy_from_slice(msg);
+        dst[msg.len()..block_len].copy_from_slice(&self.parity);
+        Ok(block_len)
+    }
+}
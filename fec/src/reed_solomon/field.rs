@@ -0,0 +1,98 @@
+use FecError;
+
+/// GF(2^8) defined by a primitive polynomial, with `2` as the primitive element
+///
+/// Addition is xor. Multiplication and division go through log and exp tables,
+/// where the exp table is doubled so that adding two logs needs no reduction.
+#[derive(Debug, Clone)]
+pub struct Field {
+    exp: [u8; 510],
+    log: [u8; 256],
+}
+
+impl Field {
+    /// Build the field for a degree 8 `primitive_polynomial`, including its
+    /// x^8 term, e.g. `0x11d` for x^8 + x^4 + x^3 + x^2 + 1
+    pub fn new(primitive_polynomial: u16) -> Result<Field, FecError> {
+        if primitive_polynomial >> 8 != 1 {
+            return Err(FecError::InvalidPolynomial);
+        }
+
+        let mut exp = [0; 510];
+        let mut log = [0; 256];
+        let mut element: u16 = 1;
+        for i in 0..255 {
+            // the powers of a primitive element visit every nonzero element once
+            if i > 0 && element == 1 {
+                return Err(FecError::InvalidPolynomial);
+            }
+            exp[i] = element as u8;
+            exp[i + 255] = element as u8;
+            log[element as usize] = i as u8;
+
+            element <<= 1;
+            if element & 0x100 != 0 {
+                element ^= primitive_polynomial;
+            }
+        }
+        if element != 1 {
+            return Err(FecError::InvalidPolynomial);
+        }
+
+        Ok(Field { exp, log })
+    }
+
+    /// `2^power`, for any power
+    #[inline]
+    pub fn exp(&self, power: usize) -> u8 {
+        self.exp[power % 255]
+    }
+
+    /// Power of `2` that gives the nonzero `x`
+    #[inline]
+    pub fn log(&self, x: u8) -> usize {
+        debug_assert!(x != 0);
+        self.log[x as usize] as usize
+    }
+
+    #[inline]
+    pub fn mul(&self, a: u8, b: u8) -> u8 {
+        if a == 0 || b == 0 {
+            return 0;
+        }
+        self.exp[self.log[a as usize] as usize + self.log[b as usize] as usize]
+    }
+
+    /// `a / b`, for a nonzero `b`
+    #[inline]
+    pub fn div(&self, a: u8, b: u8) -> u8 {
+        debug_assert!(b != 0);
+        if a == 0 {
+            return 0;
+        }
+        self.exp[self.log[a as usize] as usize + 255 - self.log[b as usize] as usize]
+    }
+
+    /// `1 / x`, for a nonzero `x`
+    #[inline]
+    pub fn inv(&self, x: u8) -> u8 {
+        self.div(1, x)
+    }
+
+    /// `x^power`, where `0^0` is 1
+    #[inline]
+    pub fn pow(&self, x: u8, power: usize) -> u8 {
+        if power == 0 {
+            return 1;
+        }
+        if x == 0 {
+            return 0;
+        }
+        self.exp((self.log(x) * power) % 255)
+    }
+
+    /// Evaluate the polynomial with coefficients `poly`, highest degree first, at `x`
+    pub fn eval(&self, poly: &[u8], x: u8) -> u8 {
+        poly.iter().fold(0, |acc, coeff| self.mul(acc, x) ^ coeff)
+    }
+}
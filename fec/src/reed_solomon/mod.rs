@@ -0,0 +1,11 @@
+pub mod codes;
+mod decoder;
+mod encoder;
+mod field;
+mod util;
+
+pub use self::codes::Code;
+pub use self::decoder::Decoder;
+pub use self::encoder::Encoder;
+pub use self::field::Field;
+pub use self::util::MAX_BLOCK_LEN;
@@ -0,0 +1,57 @@
+use super::field::Field;
+use FecError;
+
+/// Longest block, as a GF(2^8) code has 255 distinct nonzero locators
+pub const MAX_BLOCK_LEN: usize = 255;
+
+fn gcd(mut a: usize, mut b: usize) -> usize {
+    while b != 0 {
+        let remainder = a % b;
+        a = b;
+        b = remainder;
+    }
+    a
+}
+
+/// Check that the code is one we can encode and decode. The roots must leave
+/// room for at least one message byte, and the gap between them must be
+/// coprime with 255 so that every position in a block has its own locator.
+pub fn check_code(
+    first_consecutive_root: usize,
+    root_gap: usize,
+    num_roots: usize,
+) -> Result<(), FecError> {
+    if num_roots == 0 || num_roots >= MAX_BLOCK_LEN {
+        return Err(FecError::InvalidRate);
+    }
+    if first_consecutive_root >= MAX_BLOCK_LEN || root_gap == 0 || gcd(root_gap, MAX_BLOCK_LEN) != 1
+    {
+        return Err(FecError::InvalidPolynomial);
+    }
+    Ok(())
+}
+
+/// The roots `2^(root_gap * (first_consecutive_root + i))` of the generator polynomial
+pub fn generator_roots(
+    field: &Field,
+    first_consecutive_root: usize,
+    root_gap: usize,
+    num_roots: usize,
+) -> Vec<u8> {
+    (0..num_roots)
+        .map(|i| field.exp(root_gap * (first_consecutive_root + i)))
+        .collect()
+}
+
+/// Monic generator polynomial with the given roots, highest degree first
+pub fn generator_polynomial(field: &Field, roots: &[u8]) -> Vec<u8> {
+    let mut generator = vec![1];
+    for root in roots {
+        // multiply by (x - root)
+        generator.push(0);
+        for i in (1..generator.len()).rev() {
+            generator[i] ^= field.mul(generator[i - 1], *root);
+        }
+    }
+    generator
+}
@@ -0,0 +1,332 @@
+//! Linear feedback shift register scramblers
+//!
+//! Scrambling breaks up long runs of identical bits, which would otherwise
+//! leave the encoded signal without transitions and with a poor spectrum.
+//!
+//! Polynomials of `width` bits have bit `k - 1` set for the term `x^k`, which
+//! taps the bit shifted into the register `k` bits earlier; the constant term
+//! is implied. Seeds follow the same layout, bit `k - 1` holding the stage
+//! that feeds the `x^k` tap. Packed bytes are processed most significant bit
+//! first.
+
+use FecError;
+
+/// 802.11 scrambler polynomial x^7 + x^4 + 1
+const IEEE80211_POLY: u64 = 0x48;
+/// DVB energy dispersal polynomial 1 + x^14 + x^15
+const DVB_POLY: u64 = 0x6000;
+/// DVB initial register 100101010000000, stage 1 first
+const DVB_SEED: u64 = 0xa9;
+/// CCSDS randomizer polynomial x^8 + x^7 + x^5 + x^3 + 1, which taps the
+/// register from the other end
+const CCSDS_POLY: u64 = 0x95;
+/// State 8 bits before the all-ones CCSDS register, so that the sequence
+/// starts with the 0xff of the standard
+const CCSDS_SEED: u64 = 0x58;
+
+#[derive(Debug, Clone)]
+struct Lfsr {
+    poly: u64,
+    mask: u64,
+    seed: u64,
+    state: u64,
+}
+
+impl Lfsr {
+    fn new(width: u32, poly: u64, seed: u64) -> Result<Lfsr, FecError> {
+        if !(1..=64).contains(&width) {
+            return Err(FecError::InvalidOrder);
+        }
+        let mask = u64::MAX >> (64 - width);
+        if poly & !mask != 0 || poly & (1 << (width - 1)) == 0 || seed & !mask != 0 {
+            return Err(FecError::InvalidPolynomial);
+        }
+        Ok(Lfsr {
+            poly,
+            mask,
+            seed,
+            state: seed,
+        })
+    }
+
+    #[inline]
+    fn taps(&self) -> u8 {
+        ((self.state & self.poly).count_ones() & 1) as u8
+    }
+
+    #[inline]
+    fn shift(&mut self, bit: u8) {
+        self.state = ((self.state << 1) | bit as u64) & self.mask;
+    }
+}
+
+fn check_bits(data: &[u8], num_bits: usize) -> Result<(), FecError> {
+    if 8 * data.len() < num_bits {
+        return Err(FecError::InvalidLength);
+    }
+    Ok(())
+}
+
+/// Additive (synchronous) scrambler, also known as a whitener or randomizer
+///
+/// The data is XORed with the output sequence of a free-running LFSR, so
+/// descrambling is the same operation, and a bit error stays a single bit
+/// error. Both ends must start from the same state, e.g. by calling `reset`
+/// at every frame boundary.
+#[derive(Debug, Clone)]
+pub struct AdditiveScrambler {
+    lfsr: Lfsr,
+}
+
+impl AdditiveScrambler {
+    /// Create a scrambler for a polynomial of degree `width`, up to 64, whose
+    /// register starts out as `seed`. The seed must not be zero, as the
+    /// sequence would then be all zeros.
+    pub fn new(width: u32, poly: u64, seed: u64) -> Result<AdditiveScrambler, FecError> {
+        let lfsr = Lfsr::new(width, poly, seed)?;
+        if seed == 0 {
+            return Err(FecError::InvalidPolynomial);
+        }
+        Ok(AdditiveScrambler { lfsr })
+    }
+
+    /// Create the 802.11 data scrambler, x^7 + x^4 + 1, from the nonzero 7 bit
+    /// seed sent in the SERVICE field
+    pub fn ieee80211(seed: u8) -> Result<AdditiveScrambler, FecError> {
+        AdditiveScrambler::new(7, IEEE80211_POLY, seed as u64)
+    }
+
+    /// Create the DVB energy dispersal scrambler, 1 + x^14 + x^15. The
+    /// standard resets it every 8 transport packets, and leaves the sync bytes
+    /// unscrambled while the sequence keeps running.
+    pub fn dvb() -> AdditiveScrambler {
+        AdditiveScrambler::new(15, DVB_POLY, DVB_SEED).expect("DVB parameters are valid")
+    }
+
+    /// Create the CCSDS pseudo-randomizer, whose sequence starts 0xff 0x48
+    /// 0x0e 0xc0 and is reset at the start of every transfer frame
+    pub fn ccsds() -> AdditiveScrambler {
+        AdditiveScrambler::new(8, CCSDS_POLY, CCSDS_SEED).expect("CCSDS parameters are valid")
+    }
+
+    /// Restart the sequence from the seed
+    pub fn reset(&mut self) {
+        self.lfsr.state = self.lfsr.seed;
+    }
+
+    #[inline]
+    fn next_bit(&mut self) -> u8 {
+        let bit = self.lfsr.taps();
+        self.lfsr.shift(bit);
+        bit
+    }
+
+    /// Scramble or descramble packed bytes in place
+    pub fn scramble(&mut self, data: &mut [u8]) {
+        for byte in data.iter_mut() {
+            let mut sequence = 0;
+            for _ in 0..8 {
+                sequence = (sequence << 1) | self.next_bit();
+            }
+            *byte ^= sequence;
+        }
+    }
+
+    /// Scramble or descramble the first `num_bits` bits of `data` in place,
+    /// so that streams that are not whole bytes keep the sequence running
+    pub fn scramble_bits(&mut self, data: &mut [u8], num_bits: usize) -> Result<usize, FecError> {
+        check_bits(data, num_bits)?;
+        for i in 0..num_bits {
+            data[i / 8] ^= self.next_bit() << (7 - i % 8);
+        }
+        Ok(num_bits)
+    }
+
+    /// Descramble soft symbols in place, one byte per bit, where 0 is a strong
+    /// zero and 255 is a strong one, by flipping those the sequence inverted
+    pub fn scramble_soft(&mut self, soft: &mut [u8]) {
+        for symbol in soft.iter_mut() {
+            if self.next_bit() == 1 {
+                *symbol = u8::MAX - *symbol;
+            }
+        }
+    }
+}
+
+/// Multiplicative (self-synchronizing) scrambler
+///
+/// The register is fed with the scrambled bits themselves, so the
+/// descrambler falls into step with the scrambler after `width` bits no matter
+/// how it started. The price is that every bit error is repeated once for
+/// every tap of the polynomial.
+#[derive(Debug, Clone)]
+pub struct MultiplicativeScrambler {
+    lfsr: Lfsr,
+}
+
+impl MultiplicativeScrambler {
+    /// Create a scrambler or descrambler for a polynomial of degree `width`,
+    /// up to 64, whose register starts out as `seed`
+    pub fn new(width: u32, poly: u64, seed: u64) -> Result<MultiplicativeScrambler, FecError> {
+        Ok(MultiplicativeScrambler {
+            lfsr: Lfsr::new(width, poly, seed)?,
+        })
+    }
+
+    /// Restart from the seed
+    pub fn reset(&mut self) {
+        self.lfsr.state = self.lfsr.seed;
+    }
+
+    #[inline]
+    fn scramble_bit(&mut self, bit: u8) -> u8 {
+        let scrambled = bit ^ self.lfsr.taps();
+        self.lfsr.shift(scrambled);
+        scrambled
+    }
+
+    #[inline]
+    fn descramble_bit(&mut self, scrambled: u8) -> u8 {
+        let bit = scrambled ^ self.lfsr.taps();
+        self.lfsr.shift(scrambled);
+        bit
+    }
+
+    /// Scramble packed bytes in place
+    pub fn scramble(&mut self, data: &mut [u8]) {
+        let num_bits = 8 * data.len();
+        self.scramble_bits(data, num_bits)
+            .expect("whole bytes are always in range");
+    }
+
+    /// Descramble packed bytes in place
+    pub fn descramble(&mut self, data: &mut [u8]) {
+        let num_bits = 8 * data.len();
+        self.descramble_bits(data, num_bits)
+            .expect("whole bytes are always in range");
+    }
+
+    /// Scramble the first `num_bits` bits of `data` in place
+    pub fn scramble_bits(&mut self, data: &mut [u8], num_bits: usize) -> Result<usize, FecError> {
+        check_bits(data, num_bits)?;
+        for i in 0..num_bits {
+            let shift = 7 - i % 8;
+            let bit = (data[i / 8] >> shift) & 1;
+            data[i / 8] ^= (bit ^ self.scramble_bit(bit)) << shift;
+        }
+        Ok(num_bits)
+    }
+
+    /// Descramble the first `num_bits` bits of `data` in place
+    pub fn descramble_bits(&mut self, data: &mut [u8], num_bits: usize) -> Result<usize, FecError> {
+        check_bits(data, num_bits)?;
+        for i in 0..num_bits {
+            let shift = 7 - i % 8;
+            let scrambled = (data[i / 8] >> shift) & 1;
+            data[i / 8] ^= (scrambled ^ self.descramble_bit(scrambled)) << shift;
+        }
+        Ok(num_bits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AdditiveScrambler, MultiplicativeScrambler};
+    use FecError;
+
+    /// 10GBASE-R scrambler, 1 + x^39 + x^58
+    const POLY_10GBASE_R: u64 = (1 << 38) | (1 << 57);
+
+    fn test_data() -> Vec<u8> {
+        (0..100).map(|i| (i * 41 + 3) as u8).collect()
+    }
+
+    #[test]
+    fn rejects_zero_seed() {
+        assert_eq!(
+            AdditiveScrambler::new(7, 0x48, 0).err(),
+            Some(FecError::InvalidPolynomial)
+        );
+        assert_eq!(
+            AdditiveScrambler::ieee80211(0).err(),
+            Some(FecError::InvalidPolynomial)
+        );
+        assert!(AdditiveScrambler::ieee80211(0x5d).is_ok());
+    }
+
+    #[test]
+    fn known_sequences() {
+        let mut data = [0; 8];
+        AdditiveScrambler::ccsds().scramble(&mut data);
+        assert_eq!(data, [0xff, 0x48, 0x0e, 0xc0, 0x9a, 0x0d, 0x70, 0xbc]);
+
+        let mut data = [0; 4];
+        AdditiveScrambler::dvb().scramble(&mut data);
+        assert_eq!(data, [0x03, 0xf6, 0x08, 0x34]);
+    }
+
+    #[test]
+    fn additive_scrambling_twice_is_identity() {
+        let data = test_data();
+        for scrambler in &mut [
+            AdditiveScrambler::ccsds(),
+            AdditiveScrambler::dvb(),
+            AdditiveScrambler::ieee80211(0x5d).unwrap(),
+        ] {
+            let mut scrambled = data.clone();
+            scrambler.scramble(&mut scrambled);
+            assert_ne!(scrambled, data);
+            scrambler.reset();
+            scrambler.scramble(&mut scrambled);
+            assert_eq!(scrambled, data);
+
+            // soft symbols keep their reliability and follow the hard bits
+            scrambler.reset();
+            scrambler.scramble(&mut scrambled);
+            let mut soft: Vec<u8> = (0..8 * data.len())
+                .map(|i| 20 + 215 * ((scrambled[i / 8] >> (7 - i % 8)) & 1))
+                .collect();
+            scrambler.reset();
+            scrambler.scramble_soft(&mut soft);
+            for (i, symbol) in soft.iter().enumerate() {
+                let bit = (data[i / 8] >> (7 - i % 8)) & 1;
+                assert_eq!(*symbol, 20 + 215 * bit);
+            }
+        }
+    }
+
+    #[test]
+    fn multiplicative_descrambler_synchronizes() {
+        let data = test_data();
+        let mut scrambler = MultiplicativeScrambler::new(58, POLY_10GBASE_R, 0x1234_5678).unwrap();
+        let mut scrambled = data.clone();
+        scrambler.scramble(&mut scrambled);
+        assert_ne!(scrambled, data);
+
+        let mut descrambler =
+            MultiplicativeScrambler::new(58, POLY_10GBASE_R, 0x1234_5678).unwrap();
+        let mut descrambled = scrambled.clone();
+        descrambler.descramble(&mut descrambled);
+        assert_eq!(descrambled, data);
+
+        // with the wrong state, only the first 58 bits come out wrong
+        let mut descrambler = MultiplicativeScrambler::new(58, POLY_10GBASE_R, 1).unwrap();
+        let mut descrambled = scrambled.clone();
+        descrambler.descramble(&mut descrambled);
+        assert_ne!(descrambled[..8], data[..8]);
+        assert_eq!(descrambled[8..], data[8..]);
+
+        // and a channel error comes out once for each tap
+        let mut descrambler =
+            MultiplicativeScrambler::new(58, POLY_10GBASE_R, 0x1234_5678).unwrap();
+        let mut descrambled = scrambled.clone();
+        descrambled[10] ^= 0x80;
+        descrambler.descramble(&mut descrambled);
+        let errors: u32 = descrambled
+            .iter()
+            .zip(&data)
+            .map(|(a, b)| (a ^ b).count_ones())
+            .sum();
+        assert_eq!(errors, 3);
+    }
+}